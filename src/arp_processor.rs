@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use nih_plug::{context::process::ProcessContext, plugin::ProcessStatus};
 
-use crate::{params::ArpParams, MidiProcessor, MidiTransposer, NotesState};
+use crate::{params::ArpParams, transform, MidiProcessor, MidiTransposer, NotesState};
 
 pub(crate) struct ArpProcessor {
     params: Arc<ArpParams>,
@@ -19,7 +19,8 @@ impl ArpProcessor {
     pub fn new(params: Arc<ArpParams>) -> Self {
         Self {
             params,
-            notes: Vec::with_capacity(8),
+            // A chord can hold every note, the vector must never grow on the audio thread.
+            notes: Vec::with_capacity(128),
             current_index: 0,
             synced: false,
             sample_rate: 44100.0,
@@ -36,6 +37,15 @@ impl ArpProcessor {
         self.time = 0;
     }
 
+    /**
+     * Follows the retrograde param, the current note stays the same when the order is reversed.
+     */
+    fn order_notes(&mut self) {
+        if transform::order_arp_notes(&mut self.notes, self.params.retrograde.value()) {
+            self.current_index = self.notes.len() - 1 - self.current_index;
+        }
+    }
+
     pub fn process_free(
         context: &mut impl ProcessContext<MidiTransposer>,
         notes_state: &NotesState,
@@ -51,16 +61,17 @@ impl MidiProcessor for ArpProcessor {
         _notes_state: &NotesState,
         _nb_samples: usize,
     ) -> ProcessStatus {
+        self.order_notes();
         ProcessStatus::Normal
     }
 
-    fn arp_toggled(
+    fn toggled(
         &mut self,
         _context: &mut impl ProcessContext<MidiTransposer>,
-        on_off: bool,
+        active: bool,
         notes_state: &NotesState,
     ) {
-        if on_off {
+        if active {
             // Just reconstruct the chord, the notes will be handled in the next call to process.
             if let Some(current_chord) = &notes_state.current_chord {
                 for i in 0..128 {
@@ -69,6 +80,7 @@ impl MidiProcessor for ArpProcessor {
                     }
                 }
             }
+            self.order_notes();
         } else {
             // Turn off the current note.
             if !self.notes.is_empty() {
@@ -91,7 +103,7 @@ impl MidiProcessor for ArpProcessor {
 }
 
 pub struct NoteDivision {
-    label: &'static str,
+    pub label: &'static str,
     pub division: f64,
 }
 
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use nih_plug::{
+    context::process::ProcessContext, midi::NoteEvent, plugin::ProcessStatus, prelude::Enum,
+};
+
+use crate::{
+    chord_recognizer, note_info::NoteInfo, params::BassParams, MidiProcessor, MidiTransposer,
+    NotesState,
+};
+
+/**
+ * The tone of the recognized chord that is played as the bass note.
+ */
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum BassNote {
+    #[name = "Root"]
+    Root,
+    #[name = "Third"]
+    Third,
+    #[name = "Fifth"]
+    Fifth,
+    #[name = "Seventh"]
+    Seventh,
+    /**
+     * The lowest note held, to follow slash chords.
+     */
+    #[name = "Slash bass"]
+    Lowest,
+}
+
+/**
+ * The reverse of the chord processor: it recognizes the chord held on the input
+ * and only plays its bass note.
+ */
+pub(crate) struct BassProcessor {
+    params: Arc<BassParams>,
+    /**
+     * The bass note currently playing, with its channel.
+     */
+    current_note: Option<(u8, u8)>,
+}
+
+impl MidiProcessor for BassProcessor {
+    fn process(
+        &mut self,
+        context: &mut impl ProcessContext<MidiTransposer>,
+        notes_state: &NotesState,
+        _nb_samples: usize,
+    ) -> ProcessStatus {
+        if notes_state.trigger.is_some() {
+            let timing = notes_state
+                .current_note_held
+                .map(|note_info| note_info.timing)
+                .unwrap_or(0);
+            self.update_bass(context, &notes_state.notes_held, timing);
+        }
+        ProcessStatus::Normal
+    }
+
+    fn toggled(
+        &mut self,
+        context: &mut impl ProcessContext<MidiTransposer>,
+        active: bool,
+        notes_state: &NotesState,
+    ) {
+        if active {
+            self.update_bass(context, &notes_state.notes_held, 0);
+        } else {
+            self.stop_bass(context, 0);
+        }
+    }
+}
+
+impl BassProcessor {
+    pub fn new(params: Arc<BassParams>) -> Self {
+        Self {
+            params,
+            current_note: None,
+        }
+    }
+
+    fn bass_note(&self, notes_held: &[NoteInfo]) -> Option<u8> {
+        let chord = chord_recognizer::recognize(notes_held)?;
+        let pitch_class = match self.params.note.value() {
+            BassNote::Root => chord.root,
+            BassNote::Third => chord.tone(&[4, 3, 2, 5]),
+            BassNote::Fifth => chord.tone(&[7, 6, 8]),
+            BassNote::Seventh => chord.tone(&[10, 11, 9]),
+            BassNote::Lowest => notes_held.iter().map(|n| n.note).min()? % 12,
+        };
+        Some((self.params.octave.value() as u8 + 1) * 12 + pitch_class)
+    }
+
+    /**
+     * Plays the bass note of the chord held if it changed since the last call.
+     */
+    fn update_bass(
+        &mut self,
+        context: &mut impl ProcessContext<MidiTransposer>,
+        notes_held: &[NoteInfo],
+        timing: u32,
+    ) {
+        let bass_note = self.bass_note(notes_held);
+        if bass_note == self.current_note.map(|(note, _)| note) {
+            return;
+        }
+
+        self.stop_bass(context, timing);
+        if let (Some(note), Some(last_note)) = (bass_note, notes_held.last()) {
+            nih_plug::nih_trace!("BassProcessor::update_bass -> Play bass note {note}");
+            context.send_event(NoteEvent::NoteOn {
+                note,
+                channel: last_note.channel,
+                velocity: last_note.velocity,
+                voice_id: None,
+                timing,
+            });
+            self.current_note = Some((note, last_note.channel));
+        }
+    }
+
+    fn stop_bass(&mut self, context: &mut impl ProcessContext<MidiTransposer>, timing: u32) {
+        if let Some((note, channel)) = self.current_note.take() {
+            context.send_event(NoteEvent::NoteOff {
+                note,
+                channel,
+                velocity: 0.0,
+                voice_id: None,
+                timing,
+            });
+        }
+    }
+}
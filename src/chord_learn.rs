@@ -0,0 +1,95 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use nih_plug::prelude::{Param, ParamSetter};
+
+use crate::params::MidiTransposerParams;
+
+/**
+ * Shares the chords captured by the audio thread with the editor,
+ * which writes them into the params so that the host sees the automation gestures.
+ */
+#[derive(Default)]
+pub(crate) struct ChordLearn {
+    /**
+     * Set when a chord has been captured and not written yet.
+     * The notes are only written by the audio thread when it's not set, so they can't be torn.
+     */
+    pending: AtomicBool,
+    low_notes: AtomicU64,
+    high_notes: AtomicU64,
+}
+
+impl ChordLearn {
+    /**
+     * Called by the audio thread when a chord has been played and released.
+     */
+    pub fn capture(&self, notes: u128) {
+        if notes == 0 || self.pending.load(Ordering::Acquire) {
+            return;
+        }
+        self.low_notes.store(notes as u64, Ordering::Relaxed);
+        self.high_notes
+            .store((notes >> 64) as u64, Ordering::Relaxed);
+        self.pending.store(true, Ordering::Release);
+    }
+
+    fn take(&self) -> Option<u128> {
+        if !self.pending.load(Ordering::Acquire) {
+            return None;
+        }
+        let notes = self.low_notes.load(Ordering::Relaxed) as u128
+            | (self.high_notes.load(Ordering::Relaxed) as u128) << 64;
+        self.pending.store(false, Ordering::Release);
+        Some(notes)
+    }
+
+    /**
+     * Writes the captured chord into the note panel that maps its lowest note, in the zone of that note,
+     * and disarms the learn mode.
+     * The intervals are relative to the lowest note, and the octave offset compensates the global
+     * octave transpose so that the key plays back what was played.
+     */
+    pub fn apply(&self, params: &MidiTransposerParams, setter: &ParamSetter) {
+        let Some(notes) = self.take() else {
+            return;
+        };
+        let lowest = notes.trailing_zeros() as i32;
+        let zone = params.zone(lowest as u8);
+        let note_param = &zone.notes[params.note_index(lowest as u8)];
+        nih_plug::nih_log!("ChordLearn::apply -> Writing the chord learned on note {lowest}");
+
+        set_param(setter, &note_param.active, true);
+        set_param(setter, &note_param.transpose, 0);
+        set_param(
+            setter,
+            &note_param.octave,
+            (-zone.octave_transpose).clamp(-2, 2),
+        );
+
+        let mut intervals = (lowest + 1..128)
+            .filter(|note| notes & (1 << note) != 0)
+            .map(|note| note - lowest)
+            .filter(|interval| *interval <= 24);
+        for interval_param in &note_param.intervals {
+            match intervals.next() {
+                Some(interval) => {
+                    set_param(setter, &interval_param.enabled, true);
+                    set_param(setter, &interval_param.interval, interval);
+                }
+                None => set_param(setter, &interval_param.enabled, false),
+            }
+        }
+        // The velocity layers would play other chords.
+        for layer in &note_param.layers {
+            set_param(setter, &layer.activated, false);
+        }
+
+        set_param(setter, &params.learn, false);
+    }
+}
+
+pub(crate) fn set_param<P: Param>(setter: &ParamSetter, param: &P, value: P::Plain) {
+    setter.begin_set_parameter(param);
+    setter.set_parameter(param, value);
+    setter.end_set_parameter(param);
+}
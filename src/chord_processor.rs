@@ -3,97 +3,149 @@ use std::sync::Arc;
 use nih_plug::{context::process::ProcessContext, midi::NoteEvent, plugin::ProcessStatus};
 
 use crate::{
-    note_info::NoteInfo, params::MidiTransposerParams, Chord, MidiProcessor, MidiTransposer,
-    NoteTrigger, NotesState,
+    arp_processor::NOTE_DIVISIONS,
+    diatonic, key,
+    note_info::NoteInfo,
+    note_panel::NotePanel,
+    note_range::NoteRange,
+    params::{ChordParams, MidiTransposerParams},
+    pedal::Pedals,
+    random::Random,
+    scheduler::NoteScheduler,
+    strum::{self, StrumDirection},
+    velocity, voicing, Chord, MidiProcessor, MidiTransposer, NoteTrigger, NotesState,
 };
 
-#[derive(Default)]
-pub(crate) struct ChordProcessor {}
+/**
+ * Mixed into the seed, so the humanization draws from another stream than the intervals and variations.
+ */
+const HUMANIZE_STREAM: u64 = 0x6875_6d61_6e69_7a65;
+
+/**
+ * The lowest velocity of a humanized note, a note on with a velocity of 0 would be read as a note off.
+ */
+const MIN_HUMANIZED_VELOCITY: f32 = 1.0 / 127.0;
+
+pub(crate) struct ChordProcessor {
+    params: Arc<ChordParams>,
+    pub(crate) sample_rate: f32,
+    /**
+     * The strummed notes that have not been sent yet.
+     */
+    scheduler: NoteScheduler,
+    random: Random,
+    /**
+     * The direction of the next strum when it's alternating.
+     */
+    strum_up: bool,
+    pedals: Pedals,
+}
 
 impl MidiProcessor for ChordProcessor {
     fn process(
         &mut self,
         context: &mut impl ProcessContext<MidiTransposer>,
         notes_state: &NotesState,
-        _nb_samples: usize,
+        nb_samples: usize,
     ) -> ProcessStatus {
+        // The pedals are updated first, so a chord released after the pedal is lifted is stopped right away.
+        // The notes they don't hold anymore are released when the pedal is lifted.
+        self.pedals.update(
+            notes_state.sustain,
+            notes_state.sostenuto,
+            notes_state.current_chord.as_ref(),
+            |note, channel| {
+                self.scheduler
+                    .schedule_note_off(notes_state.pedal_timing, 0, note, channel)
+            },
+        );
+
         match &notes_state.trigger {
-            Some(trigger) => match trigger {
-                NoteTrigger::Play => {
-                    if let Some(note_info) = notes_state.current_note_held {
-                        if let Some(chord_to_stop) = &notes_state.previous_chord {
-                            nih_plug::nih_trace!("ChordProcessor::process -> Stop previous chord");
-                            for i in 0..128 {
-                                if chord_to_stop.notes & (1 << i) != 0 {
-                                    context.send_event(NoteEvent::NoteOff {
-                                        note: i,
-                                        channel: chord_to_stop.channel,
-                                        velocity: 0.0,
-                                        voice_id: None,
-                                        timing: note_info.timing,
-                                    });
-                                }
-                            }
-                        }
+            Some(NoteTrigger::Play) => {
+                if let Some(note_info) = notes_state.current_note_held {
+                    // In gate mode, the previous chord is stopped by its own note offs.
+                    let gate = self.params.gate.value();
+                    let mut chord_to_stop = notes_state.previous_chord.filter(|_| !gate);
+                    let mut chord_to_play = notes_state.current_chord;
 
-                        if let Some(chord_to_play) = &notes_state.current_chord {
-                            nih_plug::nih_trace!("ChordProcessor::process -> Play current chord");
-                            for i in 0..128 {
-                                if chord_to_play.notes & (1 << i) != 0 {
-                                    context.send_event(NoteEvent::NoteOn {
-                                        note: i,
-                                        channel: chord_to_play.channel,
-                                        velocity: note_info.velocity,
-                                        voice_id: None,
-                                        timing: note_info.timing,
-                                    });
-                                }
-                            }
-                        }
+                    // In legato, only the tones leaving are released and only the tones arriving are started.
+                    if let (true, Some(previous), Some(current)) = (
+                        self.params.legato.value() && !gate,
+                        chord_to_stop.as_mut(),
+                        chord_to_play.as_mut(),
+                    ) {
+                        remove_common_tones(previous, current);
                     }
-                }
-                NoteTrigger::Stop => {
-                    if let Some(chord_to_stop) = &notes_state.previous_chord {
-                        nih_plug::nih_trace!("ChordProcessor::process -> Stop current chord");
-                        for i in 0..128 {
-                            if chord_to_stop.notes & (1 << i) != 0 {
-                                context.send_event(NoteEvent::NoteOff {
-                                    note: i,
-                                    channel: chord_to_stop.channel,
-                                    velocity: 0.0,
-                                    voice_id: None,
-                                    timing: 0,
-                                });
-                            }
-                        }
+
+                    if let Some(chord_to_stop) = &chord_to_stop {
+                        nih_plug::nih_trace!("ChordProcessor::process -> Stop previous chord");
+                        self.stop_chord(chord_to_stop, note_info.timing);
+                    }
+
+                    if let Some(chord_to_play) = &chord_to_play {
+                        nih_plug::nih_trace!("ChordProcessor::process -> Play current chord");
+                        let tempo = context.transport().tempo.unwrap_or(120.0);
+                        self.strum_chord(chord_to_play, note_info.timing, tempo);
                     }
                 }
-            },
-            None => {
-                // If there's no trigger, we don't have to do anything.
-                return ProcessStatus::Normal;
             }
+            // The release of the key doesn't matter in gate mode.
+            Some(NoteTrigger::Stop) if self.params.gate.value() => {}
+            Some(NoteTrigger::Stop) => {
+                if let Some(chord_to_stop) = &notes_state.previous_chord {
+                    nih_plug::nih_trace!("ChordProcessor::process -> Stop current chord");
+                    self.stop_chord(chord_to_stop, 0);
+                }
+            }
+            // If there's no trigger, only the strummed notes left from the previous buffers are sent.
+            None => {}
+        }
+
+        // The gates that end while a pedal is down are held back like the released keys.
+        self.scheduler.flush(context, nb_samples, |note, channel| {
+            self.pedals.defer_note_off(note, channel)
+        });
+
+        // Keep being called while there are strummed notes waiting to be sent.
+        if self.scheduler.has_pending_events() {
+            ProcessStatus::KeepAlive
+        } else {
+            ProcessStatus::Normal
         }
-        ProcessStatus::Normal
     }
 
-    fn arp_toggled(
+    fn toggled(
         &mut self,
         context: &mut impl ProcessContext<MidiTransposer>,
-        on_off: bool,
+        active: bool,
         notes_state: &NotesState,
     ) {
-        // When the arp is turned on, we need to stop the current chord, and the arp will take over.
+        // The notes that have not been strummed yet will never be played,
+        // and the ones waiting for the end of their gate are released.
+        self.scheduler.release_all(context);
+
+        // Neither processor will release the notes held by the pedals later.
+        self.pedals.release_all(|note, channel| {
+            context.send_event(NoteEvent::NoteOff {
+                note,
+                channel,
+                velocity: 0.0,
+                voice_id: None,
+                timing: 0,
+            });
+        });
+
+        // When another processor takes over, we need to stop the current chord.
         // Otherwise we retrigger the chord.
-        if let Some(note_info) = notes_state.current_note_held {
-            if on_off {
-                nih_plug::nih_trace!("ChordProcessor::arp_toggled -> Stop current chord");
+        if notes_state.current_note_held.is_some() {
+            if !active {
+                nih_plug::nih_trace!("ChordProcessor::toggled -> Stop current chord");
                 if let Some(chord_to_stop) = &notes_state.current_chord {
                     for i in 0..128 {
-                        if chord_to_stop.notes & (1 << i) != 0 {
+                        for (channel, _) in chord_to_stop.voices(i) {
                             context.send_event(NoteEvent::NoteOff {
                                 note: i,
-                                channel: chord_to_stop.channel,
+                                channel,
                                 velocity: 0.0,
                                 voice_id: None,
                                 timing: 0,
@@ -102,14 +154,14 @@ impl MidiProcessor for ChordProcessor {
                     }
                 }
             } else {
-                nih_plug::nih_trace!("ChordProcessor::arp_toggled -> Restart current chord");
+                nih_plug::nih_trace!("ChordProcessor::toggled -> Restart current chord");
                 if let Some(chord_to_play) = &notes_state.current_chord {
                     for i in 0..128 {
-                        if chord_to_play.notes & (1 << i) != 0 {
+                        for (channel, velocity) in chord_to_play.voices(i) {
                             context.send_event(NoteEvent::NoteOn {
                                 note: i,
-                                channel: chord_to_play.channel,
-                                velocity: note_info.velocity,
+                                channel,
+                                velocity,
                                 voice_id: None,
                                 timing: 0,
                             });
@@ -122,47 +174,217 @@ impl MidiProcessor for ChordProcessor {
 }
 
 impl ChordProcessor {
-    pub(crate) fn build_chord(params: Arc<MidiTransposerParams>, note_info: &NoteInfo) -> Chord {
-        let mut chord = Chord {
-            notes: 0b0,
-            channel: note_info.channel,
+    pub fn new(params: Arc<ChordParams>) -> Self {
+        Self {
+            params,
+            sample_rate: 44100.0,
+            scheduler: NoteScheduler::default(),
+            random: Random::new(0x5eed),
+            strum_up: true,
+            pedals: Pedals::default(),
+        }
+    }
+
+    /**
+     * Sends the note offs of a chord, and cancels its notes that have not been strummed yet.
+     * The note offs are held back while a pedal sustains the notes.
+     */
+    fn stop_chord(&mut self, chord: &Chord, timing: u32) {
+        for i in 0..128 {
+            for (channel, _) in chord.voices(i) {
+                self.scheduler.cancel_note_on(i, channel);
+                if !self.pedals.defer_note_off(i, channel) {
+                    self.scheduler.schedule_note_off(timing, 0, i, channel);
+                }
+            }
+        }
+    }
+
+    /**
+     * Schedules the note ons of a chord, spread in time according to the strum params.
+     */
+    fn strum_chord(&mut self, chord: &Chord, timing: u32, tempo: f64) {
+        let direction = self.params.direction.value();
+        let (notes, count) = strum::strum_order(chord, direction, self.strum_up);
+        if direction == StrumDirection::Alternating {
+            self.strum_up = !self.strum_up;
+        }
+
+        let strum_samples = self.duration_samples(
+            self.params.synced.value(),
+            self.params.rate.value(),
+            self.params.time.value(),
+            tempo,
+        );
+        let gate_samples = self.params.gate.value().then(|| {
+            self.duration_samples(
+                self.params.gate_synced.value(),
+                self.params.gate_rate.value(),
+                self.params.gate_time.value(),
+                tempo,
+            )
+        });
+        let humanize_samples = self.params.humanize_timing.value() / 1000.0 * self.sample_rate;
+        let humanize_velocity = self.params.humanize_velocity.value();
+
+        for (index, note) in notes[..count].iter().enumerate() {
+            // A note doubled on several channels is strummed at once on all of them.
+            let delay = index as f32 * strum_samples + self.random.next_f32() * humanize_samples;
+            for (channel, velocity) in chord.voices(*note) {
+                // A note still ringing because of a pedal or its gate is released before being played again.
+                let still_ringing = self.pedals.take_sustained_note(*note, channel);
+                if self.scheduler.cancel_note_off(*note, channel) || still_ringing {
+                    self.scheduler.schedule_note_off(timing, 0, *note, channel);
+                }
+                let velocity = (velocity + self.random.next_bipolar() * humanize_velocity)
+                    .clamp(MIN_HUMANIZED_VELOCITY, 1.0);
+                self.scheduler
+                    .schedule_note_on(timing, delay as u32, *note, channel, velocity);
+                if let Some(gate_samples) = gate_samples {
+                    self.scheduler.schedule_gate_off(
+                        timing,
+                        (delay + gate_samples) as u32,
+                        *note,
+                        channel,
+                    );
+                }
+            }
+        }
+    }
+
+    /**
+     * Converts a time in ms, or a note division when it's synced to the tempo, to samples.
+     */
+    fn duration_samples(&self, synced: bool, rate: i32, time: f32, tempo: f64) -> f32 {
+        if synced {
+            let division = NOTE_DIVISIONS[rate as usize].division;
+            (division * 60.0 / tempo * self.sample_rate as f64) as f32
+        } else {
+            time / 1000.0 * self.sample_rate
+        }
+    }
+
+    /**
+     * Reseeds the random generator, so the humanization is the same on each render.
+     */
+    pub fn reset(&mut self, seed: u64) {
+        self.random = Random::new(seed ^ HUMANIZE_STREAM);
+    }
+
+    pub(crate) fn build_chord(
+        params: Arc<MidiTransposerParams>,
+        note_info: &NoteInfo,
+        random: &mut Random,
+    ) -> Chord {
+        let mut chord = Chord::default();
+        let zone = params.zone(note_info.note);
+        // The key shift moves the whole mapping, each key plays the chord of the key it replaces, transposed.
+        let key_shift = params.key_shift.value();
+        let note_param = &zone.notes[params.note_index(note_info.note)];
+        // While the diatonic fill is armed, the main note map plays the chords it's about to write.
+        let note_panel = if params.diatonic.fill.value() && !params.in_upper_zone(note_info.note) {
+            diatonic::fill_panel(&params, note_param, note_info.note % 12)
+        } else {
+            NotePanel::from_params(note_param)
         };
-        let base_note = note_info.note % 12;
+        let velocity = velocity::base_velocity(&params, note_info.velocity);
+        let range = NoteRange::from_params(&params);
 
         // Exit if the transposition is deactivated for this note.
-        if !params.notes[base_note as usize].active.value() {
+        if !note_panel.active {
             // Just play the base note.
-            chord.notes |= 1 << note_info.note;
+            if let Some(note) = range.fit(note_info.note as i32) {
+                chord.add_note(note, velocity, note_info.channel);
+            }
             return chord;
         }
 
-        // Create a copy of the note info to map with the transposition.
-        let note_transpose = params.notes[base_note as usize].transpose.value() as i8;
-        let mapped_note_info = note_info.with_transposition(note_transpose);
+        // Map the base note with the transposition.
+        // The computed notes can go out of the midi range until the range policy is applied.
+        let mapped_note = note_info.note as i32 + note_panel.transpose;
+        let root_velocity = velocity::scale(velocity, note_panel.root_velocity);
+        let root_channel = output_channel(note_panel.root_channel, note_info.channel);
 
-        // Include the base note at its original octave if there's an octave transpose.
-        let octave_transpose = params.octave_transpose.value();
-        if octave_transpose != 0 {
-            chord.notes |= 1 << mapped_note_info.note;
+        // Include the base note at the transposed octave.
+        let octave_transpose = zone.octave_transpose + note_panel.octave;
+        let transposed_root = mapped_note + 12 * octave_transpose;
+        if let Some(note) = range.fit(transposed_root) {
+            chord.add_note(note, root_velocity, root_channel);
         }
-        // Also include the base note at the transposed octave.
-        chord.notes |= 1 << (mapped_note_info.note + 12 * octave_transpose as u8);
 
-        // For each interval defined in the params, add the corresponding note,
+        // For each interval enabled in the note panel, add the corresponding note,
         // based on the base note and the transposition.
-        params.notes[base_note as usize]
+        // The intervals come from the velocity layer matching the note played, if there's one.
+        let layer = note_panel.velocity_layer(note_info.velocity);
+        note_panel
             .intervals
             .iter()
-            .map(|interval_param| {
-                (mapped_note_info.note as i32
-                    + octave_transpose as i32 * 12
-                    + interval_param.interval.value()) as u8
+            .enumerate()
+            .filter_map(|(slot, panel_interval)| {
+                let interval = note_panel.interval(slot, layer)?;
+                // An interval of 0 that follows the chord channel is the root itself, which keeps its own
+                // velocity and channel. Routed to another channel, it doubles the root there.
+                if interval == 0 && panel_interval.channel == 0 {
+                    return None;
+                }
+                if random.next_f32() >= panel_interval.probability {
+                    return None;
+                }
+                range.fit(transposed_root + interval).map(|note| {
+                    (
+                        note,
+                        velocity::scale(velocity, panel_interval.velocity),
+                        output_channel(panel_interval.channel, note_info.channel),
+                    )
+                })
             })
-            .filter(|note| *note < 128)
-            .for_each(|note| {
-                chord.notes |= 1 << note;
+            .for_each(|(note, velocity, channel)| {
+                chord.add_note(note, velocity, channel);
             });
 
-        chord
+        // Rearrange the stacked chord according to the inversion and voicing.
+        // The voicing can move notes out of the register, so the range policy is applied again.
+        let (inversion, voicing) = voicing::vary(
+            params.inversion.value(),
+            params.voicing.value(),
+            params.variation.value(),
+            random,
+        );
+        chord = voicing::apply(&chord, inversion, voicing);
+        chord = range.fit_chord(&chord);
+
+        // Also include the base note at its original octave if there's an octave transpose
+        // and the note wants to keep it. It's added after the voicing so that it stays in the bass.
+        if octave_transpose != 0 && note_panel.keep_root {
+            if let Some(note) = range.fit(mapped_note) {
+                chord.add_note(note, root_velocity, root_channel);
+            }
+        }
+
+        // The mapping is written in the major key of the key center, it's re-spelled in the mode shift.
+        let tonic = params.key_center.value() + key_shift;
+        key::respell(&chord, tonic, params.mode_shift.value(), &range)
+    }
+}
+
+/**
+ * Returns the channel a note is routed to: 0 follows the output channel of the chord,
+ * otherwise it's the channel param - 1 (because channels go from 0 to 15).
+ */
+fn output_channel(channel_param: i32, chord_channel: u8) -> u8 {
+    match channel_param {
+        0 => chord_channel,
+        _ => channel_param as u8 - 1,
+    }
+}
+
+/**
+ * Removes from both chords the notes they play on the same channel.
+ */
+fn remove_common_tones(previous: &mut Chord, current: &mut Chord) {
+    for i in 0..128u8 {
+        let common_channels = previous.channels[i as usize] & current.channels[i as usize];
+        previous.remove_channels(i, common_channels);
+        current.remove_channels(i, common_channels);
     }
 }
@@ -0,0 +1,111 @@
+use crate::{note_info::NoteInfo, Chord};
+
+/**
+ * The chord shapes that can be recognized, as bitmasks of intervals from the root.
+ * Bigger chords come first so they win over their subsets with the same score.
+ */
+const CHORD_TEMPLATES: [u16; 16] = [
+    0b1000_1001_0001, // maj7 (0, 4, 7, 11)
+    0b0100_1001_0001, // 7 (0, 4, 7, 10)
+    0b0100_1000_1001, // m7 (0, 3, 7, 10)
+    0b1000_1000_1001, // m(maj7) (0, 3, 7, 11)
+    0b0100_0100_1001, // m7b5 (0, 3, 6, 10)
+    0b0010_0100_1001, // dim7 (0, 3, 6, 9)
+    0b0010_1001_0001, // 6 (0, 4, 7, 9)
+    0b0010_1000_1001, // m6 (0, 3, 7, 9)
+    0b0000_1001_0001, // maj (0, 4, 7)
+    0b0000_1000_1001, // min (0, 3, 7)
+    0b0000_0100_1001, // dim (0, 3, 6)
+    0b0001_0001_0001, // aug (0, 4, 8)
+    0b0000_1010_0001, // sus4 (0, 5, 7)
+    0b0000_1000_0101, // sus2 (0, 2, 7)
+    0b0000_1000_0001, // power chord (0, 7)
+    0b0000_0000_0001, // single note
+];
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RecognizedChord {
+    /**
+     * The pitch class of the root, from 0 (C) to 11 (B).
+     */
+    pub root: u8,
+    /**
+     * The bitmask of the intervals of the chord, relative to the root.
+     */
+    pub intervals: u16,
+}
+
+impl RecognizedChord {
+    /**
+     * Returns the pitch class of the first chord tone found among the candidate intervals,
+     * or the root if the chord has none of them.
+     */
+    pub fn tone(&self, candidates: &[u8]) -> u8 {
+        candidates
+            .iter()
+            .find(|interval| self.intervals & (1 << **interval) != 0)
+            .map(|interval| (self.root + interval) % 12)
+            .unwrap_or(self.root)
+    }
+}
+
+/**
+ * Finds the chord that best matches the notes held, whatever their octave or order.
+ */
+pub(crate) fn recognize(notes_held: &[NoteInfo]) -> Option<RecognizedChord> {
+    let lowest_note = notes_held.iter().map(|n| n.note).min()?;
+    let pitch_classes = notes_held
+        .iter()
+        .fold(0u16, |classes, n| classes | (1 << (n.note % 12)));
+    recognize_pitch_classes(pitch_classes, lowest_note)
+}
+
+/**
+ * Finds the chord that best matches the notes of a chord.
+ */
+pub(crate) fn recognize_chord(chord: &Chord) -> Option<RecognizedChord> {
+    let lowest_note = (chord.notes != 0).then(|| chord.notes.trailing_zeros() as u8)?;
+    let pitch_classes = (0..128)
+        .filter(|note| chord.contains(*note))
+        .fold(0u16, |classes, note| classes | (1 << (note % 12)));
+    recognize_pitch_classes(pitch_classes, lowest_note)
+}
+
+/**
+ * Finds the chord that best matches a set of pitch classes. The lowest note breaks the ties.
+ */
+fn recognize_pitch_classes(pitch_classes: u16, lowest_note: u8) -> Option<RecognizedChord> {
+    let mut best: Option<(i32, RecognizedChord)> = None;
+    for root in (0..12u8).filter(|root| pitch_classes & (1 << root) != 0) {
+        // Rotate the pitch classes so that the candidate root is the first bit.
+        let rotated = ((pitch_classes >> root) | (pitch_classes << (12 - root))) & 0xFFF;
+        for template in CHORD_TEMPLATES {
+            if rotated & template != template {
+                continue;
+            }
+            // Every tone of the template is held, penalize the extra notes.
+            let score =
+                2 * template.count_ones() as i32 - (rotated & !template).count_ones() as i32;
+            let is_better = match best {
+                None => true,
+                Some((best_score, best_chord)) => {
+                    score > best_score
+                        // On a tie, the root played in the bass wins.
+                        || (score == best_score
+                            && root == lowest_note % 12
+                            && best_chord.root != lowest_note % 12)
+                }
+            };
+            if is_better {
+                best = Some((
+                    score,
+                    RecognizedChord {
+                        root,
+                        intervals: template,
+                    },
+                ));
+            }
+        }
+    }
+    best.map(|(_, chord)| chord)
+}
@@ -0,0 +1,223 @@
+use crate::{
+    note_info::NoteInfo, note_range::NoteRange, params::MidiTransposerParams, random::Random,
+    velocity, voicing, Chord,
+};
+
+/**
+ * The chord qualities understood in a chord symbol, with their intervals above the root.
+ */
+const QUALITIES: [(&str, &[u8]); 47] = [
+    ("", &[4, 7]),
+    ("M", &[4, 7]),
+    ("maj", &[4, 7]),
+    ("m", &[3, 7]),
+    ("min", &[3, 7]),
+    ("-", &[3, 7]),
+    ("dim", &[3, 6]),
+    ("°", &[3, 6]),
+    ("aug", &[4, 8]),
+    ("+", &[4, 8]),
+    ("5", &[7]),
+    ("sus2", &[2, 7]),
+    ("sus4", &[5, 7]),
+    ("sus", &[5, 7]),
+    ("6", &[4, 7, 9]),
+    ("m6", &[3, 7, 9]),
+    ("7", &[4, 7, 10]),
+    ("maj7", &[4, 7, 11]),
+    ("M7", &[4, 7, 11]),
+    ("Δ", &[4, 7, 11]),
+    ("m7", &[3, 7, 10]),
+    ("min7", &[3, 7, 10]),
+    ("-7", &[3, 7, 10]),
+    ("mMaj7", &[3, 7, 11]),
+    ("mM7", &[3, 7, 11]),
+    ("m7b5", &[3, 6, 10]),
+    ("ø", &[3, 6, 10]),
+    ("dim7", &[3, 6, 9]),
+    ("°7", &[3, 6, 9]),
+    ("7sus4", &[5, 7, 10]),
+    ("aug7", &[4, 8, 10]),
+    ("7#5", &[4, 8, 10]),
+    ("9", &[4, 7, 10, 14]),
+    ("maj9", &[4, 7, 11, 14]),
+    ("M9", &[4, 7, 11, 14]),
+    ("m9", &[3, 7, 10, 14]),
+    ("-9", &[3, 7, 10, 14]),
+    ("add9", &[4, 7, 14]),
+    ("madd9", &[3, 7, 14]),
+    ("7b9", &[4, 7, 10, 13]),
+    ("7#9", &[4, 7, 10, 15]),
+    ("11", &[4, 7, 10, 14, 17]),
+    ("m11", &[3, 7, 10, 14, 17]),
+    ("13", &[4, 7, 10, 14, 21]),
+    ("maj13", &[4, 7, 11, 14, 21]),
+    ("m13", &[3, 7, 10, 14, 21]),
+    ("6/9", &[4, 7, 9, 14]),
+];
+
+/**
+ * A chord written as a symbol, like "Am7", "F#dim" or "C/E".
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ChordSymbol {
+    /**
+     * The pitch class of the root, from 0 (C) to 11 (B).
+     */
+    pub root: u8,
+    /**
+     * The intervals above the root, as a bitmask of semitones.
+     */
+    pub intervals: u32,
+    /**
+     * The pitch class of the bass note of a slash chord.
+     */
+    pub bass: Option<u8>,
+}
+
+/**
+ * Parses a chord symbol, without allocating so it can be done on the audio thread.
+ */
+pub(crate) fn parse(symbol: &str) -> Option<ChordSymbol> {
+    let (root, rest) = parse_note(symbol)?;
+    // A "6/9" is not a slash chord.
+    let (quality, bass) = match rest.rsplit_once('/') {
+        Some((quality, bass)) if rest != "6/9" => (quality, Some(parse_note(bass)?)),
+        _ => (rest, None),
+    };
+    let (_, intervals) = QUALITIES.iter().find(|(name, _)| *name == quality)?;
+    let bass = match bass {
+        Some((bass, "")) => Some(bass),
+        Some(_) => return None,
+        None => None,
+    };
+    Some(ChordSymbol {
+        root,
+        intervals: intervals.iter().fold(0, |mask, i| mask | 1 << i),
+        bass,
+    })
+}
+
+/**
+ * Parses a note name with its accidental, and returns its pitch class with the rest of the text.
+ */
+pub(crate) fn parse_note(text: &str) -> Option<(u8, &str)> {
+    let mut chars = text.chars();
+    let natural: i32 = match chars.next()? {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let rest = chars.as_str();
+    let (accidental, rest) = match rest.chars().next() {
+        Some('#') | Some('♯') => (1, &rest[rest.chars().next()?.len_utf8()..]),
+        Some('b') | Some('♭') => (-1, &rest[rest.chars().next()?.len_utf8()..]),
+        _ => (0, rest),
+    };
+    Some(((natural + accidental).rem_euclid(12) as u8, rest))
+}
+
+/**
+ * Builds the chord of a chord symbol from its root note.
+ * The velocity, register and voicing are applied as for the mapped chords.
+ */
+pub(crate) fn build_chord(
+    params: &MidiTransposerParams,
+    symbol: &ChordSymbol,
+    root: i32,
+    note_info: &NoteInfo,
+    random: &mut Random,
+) -> Chord {
+    let mut chord = Chord::default();
+    let velocity = velocity::base_velocity(params, note_info.velocity);
+    let range = NoteRange::from_params(params);
+
+    for interval in (0..32).filter(|i| i == &0 || symbol.intervals & (1 << i) != 0) {
+        if let Some(note) = range.fit(root + interval) {
+            chord.add_note(note, velocity, note_info.channel);
+        }
+    }
+
+    let (inversion, voicing) = voicing::vary(
+        params.inversion.value(),
+        params.voicing.value(),
+        params.variation.value(),
+        random,
+    );
+    chord = voicing::apply(&chord, inversion, voicing);
+    chord = range.fit_chord(&chord);
+
+    // The bass of a slash chord is added below the root after the voicing, so that it stays in the bass.
+    if let Some(bass) = symbol.bass.filter(|bass| *bass != symbol.root) {
+        let bass_note = root - (symbol.root as i32 - bass as i32).rem_euclid(12);
+        if let Some(note) = range.fit(bass_note) {
+            chord.add_note(note, velocity, note_info.channel);
+        }
+    }
+    chord
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn intervals(symbol: &ChordSymbol) -> Vec<u32> {
+        (0..32)
+            .filter(|i| symbol.intervals & (1 << i) != 0)
+            .collect()
+    }
+
+    #[test]
+    fn parses_the_root_and_quality() {
+        let symbol = parse("Am7").unwrap();
+        assert_eq!(symbol.root, 9);
+        assert_eq!(intervals(&symbol), [3, 7, 10]);
+        assert_eq!(symbol.bass, None);
+
+        let symbol = parse("C").unwrap();
+        assert_eq!(symbol.root, 0);
+        assert_eq!(intervals(&symbol), [4, 7]);
+    }
+
+    #[test]
+    fn parses_the_accidentals() {
+        assert_eq!(parse("F#dim").unwrap().root, 6);
+        assert_eq!(parse("Bbmaj7").unwrap().root, 10);
+        assert_eq!(parse("Cb").unwrap().root, 11);
+        assert_eq!(parse("E♭m").unwrap().root, 3);
+    }
+
+    #[test]
+    fn parses_slash_chords() {
+        let symbol = parse("C/E").unwrap();
+        assert_eq!(symbol.root, 0);
+        assert_eq!(intervals(&symbol), [4, 7]);
+        assert_eq!(symbol.bass, Some(4));
+
+        assert_eq!(parse("Fmaj7/C").unwrap().bass, Some(0));
+        assert_eq!(parse("Dm7/Bb").unwrap().bass, Some(10));
+    }
+
+    #[test]
+    fn six_nine_is_not_a_slash_chord() {
+        let symbol = parse("C6/9").unwrap();
+        assert_eq!(intervals(&symbol), [4, 7, 9, 14]);
+        assert_eq!(symbol.bass, None);
+    }
+
+    #[test]
+    fn rejects_invalid_symbols() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("H7"), None);
+        assert_eq!(parse("cm"), None);
+        assert_eq!(parse("Cxyz"), None);
+        assert_eq!(parse("C/"), None);
+        assert_eq!(parse("C/H"), None);
+        assert_eq!(parse("C/E7"), None);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::params::DebounceParams;
+
+/**
+ * Filters the bounces and ghost notes of the input, before they trigger chords.
+ */
+pub(crate) struct Debouncer {
+    pub(crate) sample_rate: f32,
+    /**
+     * The position in samples since the plugin started.
+     */
+    time: u64,
+    /**
+     * The positions of the last accepted note on and of the last note off of each key.
+     */
+    last_note_on: [Option<u64>; 128],
+    last_note_off: [Option<u64>; 128],
+    /**
+     * The keys with an accepted note on that have not been released yet.
+     */
+    held: u128,
+}
+
+impl Default for Debouncer {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44100.0,
+            time: 0,
+            last_note_on: [None; 128],
+            last_note_off: [None; 128],
+            held: 0,
+        }
+    }
+}
+
+impl Debouncer {
+    /**
+     * Returns false if the note on is too soft, or too close to the previous note on or note off of its key.
+     */
+    pub fn accept_note_on(
+        &mut self,
+        params: &DebounceParams,
+        note: u8,
+        velocity: f32,
+        timing: u32,
+    ) -> bool {
+        let now = self.time + timing as u64;
+        let within = |last: Option<u64>, time: f32| {
+            last.is_some_and(|last| ((now - last) as f32) < time / 1000.0 * self.sample_rate)
+        };
+        if velocity < params.velocity_threshold.value()
+            || within(
+                self.last_note_on[note as usize],
+                params.retrigger_time.value(),
+            )
+            || within(
+                self.last_note_off[note as usize],
+                params.release_guard.value(),
+            )
+        {
+            nih_plug::nih_trace!("Debouncer::accept_note_on -> Ignore note {note}");
+            return false;
+        }
+        self.last_note_on[note as usize] = Some(now);
+        self.held |= 1 << note;
+        true
+    }
+
+    pub fn note_off(&mut self, note: u8, timing: u32) {
+        self.last_note_off[note as usize] = Some(self.time + timing as u64);
+        self.held &= !(1 << note);
+    }
+
+    pub fn is_held(&self, note: u8) -> bool {
+        self.held & (1 << note) != 0
+    }
+
+    /**
+     * Moves the time forward at the end of a buffer.
+     */
+    pub fn advance(&mut self, nb_samples: usize) {
+        self.time += nb_samples as u64;
+    }
+}
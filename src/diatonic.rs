@@ -0,0 +1,362 @@
+use nih_plug::prelude::{Enum, ParamSetter};
+
+use crate::{
+    chord_learn::set_param,
+    key::Mode,
+    note_panel::NotePanel,
+    params::{MidiTransposerParams, NoteParam},
+};
+
+/**
+ * The number of chord tones stacked in thirds above the root.
+ */
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum ChordSize {
+    #[name = "Triads"]
+    Triad,
+    #[name = "Seventh chords"]
+    Seventh,
+    #[name = "Ninth chords"]
+    Ninth,
+}
+
+impl ChordSize {
+    fn nb_intervals(self) -> usize {
+        match self {
+            ChordSize::Triad => 2,
+            ChordSize::Seventh => 3,
+            ChordSize::Ninth => 4,
+        }
+    }
+}
+
+/**
+ * What the keys out of the scale play.
+ */
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum OutOfScale {
+    /**
+     * The note panel is deactivated, the key plays its own note.
+     */
+    #[name = "Mute"]
+    Mute,
+    /**
+     * The chord of a parallel mode that has the note, the minor one first.
+     */
+    #[name = "Borrowed chord"]
+    Borrowed,
+    /**
+     * A dominant chord resolving to the degree a semitone away: the V7 of the degree above,
+     * whose third is the key, or the tritone substitute of the degree below, rooted on the key,
+     * when the degree above is diminished.
+     */
+    #[name = "Secondary dominant"]
+    SecondaryDominant,
+}
+
+/**
+ * The parallel modes the borrowed chords are taken from, in order of preference.
+ */
+const BORROWED_MODES: [Mode; 7] = [
+    Mode::Aeolian,
+    Mode::Ionian,
+    Mode::Dorian,
+    Mode::Mixolydian,
+    Mode::Phrygian,
+    Mode::Lydian,
+    Mode::Locrian,
+];
+
+/**
+ * The chord played by a pitch class: the root is the key transposed, with intervals stacked above it.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct FillChord {
+    pub transpose: i32,
+    pub intervals: [i32; 4],
+    pub nb_intervals: usize,
+}
+
+/**
+ * Returns the chord played by a pitch class, or None if the key must be muted.
+ */
+pub(crate) fn fill_chord(
+    key: i32,
+    mode: Mode,
+    size: ChordSize,
+    out_of_scale: OutOfScale,
+    pitch_class: i32,
+) -> Option<FillChord> {
+    let interval = (pitch_class - key).rem_euclid(12);
+    let nb_intervals = size.nb_intervals();
+    let chord_mode = if mode.scale().contains(&interval) {
+        mode
+    } else {
+        match out_of_scale {
+            OutOfScale::Mute => return None,
+            OutOfScale::Borrowed => *BORROWED_MODES
+                .iter()
+                .find(|borrowed| borrowed.scale().contains(&interval))?,
+            OutOfScale::SecondaryDominant => {
+                let (transpose, _) = secondary_dominant(mode, interval)?;
+                return Some(FillChord {
+                    transpose,
+                    intervals: [4, 7, 10, 14],
+                    nb_intervals,
+                });
+            }
+        }
+    };
+
+    Some(FillChord {
+        transpose: 0,
+        intervals: stack_thirds(chord_mode, interval)?,
+        nb_intervals,
+    })
+}
+
+/**
+ * Stacks the thirds of the scale above the degree of a root, given as an interval above the tonic.
+ */
+fn stack_thirds(mode: Mode, root: i32) -> Option<[i32; 4]> {
+    let scale = mode.scale();
+    let degree = scale.iter().position(|tone| *tone == root)?;
+    Some(core::array::from_fn(|i| {
+        let tone_degree = degree + 2 * (i + 1);
+        scale[tone_degree % 7] + 12 * (tone_degree / 7) as i32 - root
+    }))
+}
+
+/**
+ * Returns the transpose of the dominant played by a key out of the scale, with the degree it resolves to,
+ * both as intervals above the tonic. The degrees around a key out of the scale are a semitone away,
+ * a diminished degree is never the target.
+ */
+fn secondary_dominant(mode: Mode, interval: i32) -> Option<(i32, i32)> {
+    let resolves =
+        |target: i32| stack_thirds(mode, target).is_some_and(|intervals| intervals[..2] != [3, 6]);
+    let above = (interval + 1) % 12;
+    if resolves(above) {
+        // The key is the leading tone of the degree above, the third of its V7.
+        return Some((-4, above));
+    }
+    let below = (interval + 11) % 12;
+    // The tritone substitute of the V7 of the degree below is rooted on the key.
+    resolves(below).then_some((0, below))
+}
+
+/**
+ * The note panel the fill writes for a pitch class, from the default values of the panel,
+ * so the keys play the same chords while the fill is armed and once it's written.
+ * The muted keys play their own note.
+ */
+pub(crate) fn fill_panel(
+    params: &MidiTransposerParams,
+    note_param: &NoteParam,
+    pitch_class: u8,
+) -> NotePanel {
+    let mut panel = NotePanel::defaults(note_param);
+    let Some(chord) = fill_chord(
+        params.diatonic.key.value(),
+        params.diatonic.mode.value(),
+        params.diatonic.size.value(),
+        params.diatonic.out_of_scale.value(),
+        pitch_class as i32,
+    ) else {
+        panel.active = false;
+        return panel;
+    };
+
+    panel.active = true;
+    panel.transpose = chord.transpose;
+    for (slot, interval) in chord.intervals[..chord.nb_intervals].iter().enumerate() {
+        panel.intervals[slot].enabled = true;
+        panel.intervals[slot].interval = *interval;
+    }
+    // The velocity layers would play other chords.
+    for layer in &mut panel.layers {
+        layer.activated = false;
+    }
+    panel
+}
+
+/**
+ * Writes the diatonic chords into the note panels of the active bank when the fill param is armed,
+ * then disarms it, so the chords can be edited. The params can only be written from the editor,
+ * until then the chord processor plays the same panels with `fill_panel`.
+ */
+pub(crate) fn apply(params: &MidiTransposerParams, setter: &ParamSetter) {
+    if !params.diatonic.fill.value() {
+        return;
+    }
+    let key = params.diatonic.key.value();
+    nih_plug::nih_log!("diatonic::apply -> Filling the note map in the key of {key}");
+
+    for pitch_class in 0..12 {
+        // The chords are written where the keys find them with the key shift.
+        let note_param = &params.bank_notes()[params.note_index(pitch_class)];
+        fill_panel(params, note_param, pitch_class).write(note_param, setter);
+    }
+
+    set_param(setter, &params.diatonic.fill, false);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const C: i32 = 0;
+
+    fn chord(
+        key: i32,
+        mode: Mode,
+        size: ChordSize,
+        out_of_scale: OutOfScale,
+        pitch_class: i32,
+    ) -> Option<Vec<i32>> {
+        fill_chord(key, mode, size, out_of_scale, pitch_class)
+            .map(|chord| chord.intervals[..chord.nb_intervals].to_vec())
+    }
+
+    fn triad(key: i32, mode: Mode, pitch_class: i32) -> Option<Vec<i32>> {
+        chord(key, mode, ChordSize::Triad, OutOfScale::Mute, pitch_class)
+    }
+
+    #[test]
+    fn the_tonic_triad_follows_the_mode() {
+        let tonic_triads = [
+            (Mode::Ionian, [4, 7]),
+            (Mode::Dorian, [3, 7]),
+            (Mode::Phrygian, [3, 7]),
+            (Mode::Lydian, [4, 7]),
+            (Mode::Mixolydian, [4, 7]),
+            (Mode::Aeolian, [3, 7]),
+            (Mode::Locrian, [3, 6]),
+        ];
+        for (mode, intervals) in tonic_triads {
+            assert_eq!(triad(C, mode, C), Some(intervals.to_vec()), "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn stacks_the_thirds_of_the_scale_on_each_degree() {
+        assert_eq!(triad(C, Mode::Ionian, 2), Some(vec![3, 7]));
+        assert_eq!(triad(C, Mode::Ionian, 7), Some(vec![4, 7]));
+        assert_eq!(triad(C, Mode::Ionian, 11), Some(vec![3, 6]));
+        assert_eq!(triad(C, Mode::Aeolian, 7), Some(vec![3, 7]));
+        assert_eq!(triad(C, Mode::Dorian, 5), Some(vec![4, 7]));
+    }
+
+    #[test]
+    fn follows_the_key() {
+        // F# is the leading tone of G major.
+        assert_eq!(triad(7, Mode::Ionian, 6), Some(vec![3, 6]));
+        assert_eq!(triad(7, Mode::Ionian, 0), Some(vec![4, 7]));
+    }
+
+    #[test]
+    fn builds_the_seventh_and_ninth_chords() {
+        let seventh = |pitch_class| {
+            chord(
+                C,
+                Mode::Ionian,
+                ChordSize::Seventh,
+                OutOfScale::Mute,
+                pitch_class,
+            )
+        };
+        assert_eq!(seventh(0), Some(vec![4, 7, 11]));
+        assert_eq!(seventh(7), Some(vec![4, 7, 10]));
+        assert_eq!(seventh(11), Some(vec![3, 6, 10]));
+
+        let ninth = chord(2, Mode::Dorian, ChordSize::Ninth, OutOfScale::Mute, 2);
+        assert_eq!(ninth, Some(vec![3, 7, 10, 14]));
+    }
+
+    #[test]
+    fn mutes_the_keys_out_of_the_scale() {
+        assert_eq!(triad(C, Mode::Ionian, 1), None);
+        assert_eq!(triad(C, Mode::Ionian, 6), None);
+    }
+
+    #[test]
+    fn borrows_from_the_parallel_modes() {
+        let borrowed = |pitch_class| {
+            chord(
+                C,
+                Mode::Ionian,
+                ChordSize::Triad,
+                OutOfScale::Borrowed,
+                pitch_class,
+            )
+        };
+        // Eb and Ab major from C minor.
+        assert_eq!(borrowed(3), Some(vec![4, 7]));
+        assert_eq!(borrowed(8), Some(vec![4, 7]));
+        // Db major from C Phrygian, F# diminished from C Lydian.
+        assert_eq!(borrowed(1), Some(vec![4, 7]));
+        assert_eq!(borrowed(6), Some(vec![3, 6]));
+    }
+
+    #[test]
+    fn plays_secondary_dominants_on_the_keys_out_of_the_scale() {
+        let dominant = |size, pitch_class| {
+            fill_chord(
+                C,
+                Mode::Ionian,
+                size,
+                OutOfScale::SecondaryDominant,
+                pitch_class,
+            )
+        };
+        // A is in C major, it's not a secondary dominant.
+        let in_scale = dominant(ChordSize::Triad, 9).unwrap();
+        assert_eq!(in_scale.transpose, 0);
+        assert_eq!(in_scale.intervals[..2], [3, 7]);
+
+        // C# plays A7, the V7 of D.
+        let sizes = [
+            (ChordSize::Triad, &[4, 7][..]),
+            (ChordSize::Seventh, &[4, 7, 10][..]),
+            (ChordSize::Ninth, &[4, 7, 10, 14][..]),
+        ];
+        for (size, intervals) in sizes {
+            let chord = dominant(size, 1).unwrap();
+            assert_eq!(chord.transpose, -4);
+            assert_eq!(&chord.intervals[..chord.nb_intervals], intervals);
+        }
+    }
+
+    #[test]
+    fn the_secondary_dominants_resolve_to_a_degree_of_the_scale() {
+        // The key, the root of its dominant and the degree it resolves to.
+        let dominants = [
+            (Mode::Ionian, 1, 9, 2),
+            (Mode::Ionian, 3, 11, 4),
+            (Mode::Ionian, 6, 2, 7),
+            (Mode::Ionian, 8, 4, 9),
+            // B is diminished, A# is the tritone substitute resolving to A.
+            (Mode::Ionian, 10, 10, 9),
+            // D is diminished, Db is the tritone substitute resolving to C.
+            (Mode::Aeolian, 1, 1, 0),
+            (Mode::Aeolian, 4, 0, 5),
+            (Mode::Aeolian, 11, 7, 0),
+        ];
+        for (mode, interval, root, target) in dominants {
+            let (transpose, resolution) = secondary_dominant(mode, interval).unwrap();
+            assert_eq!(
+                (interval + transpose).rem_euclid(12),
+                root,
+                "{mode:?} {interval}"
+            );
+            assert_eq!(resolution, target, "{mode:?} {interval}");
+            assert!(mode.scale().contains(&target));
+            // A V7 resolves a fifth below, its tritone substitute a semitone below.
+            let fall = (root - target).rem_euclid(12);
+            assert!(fall == 7 || fall == 1, "{mode:?} {interval}");
+            // The dominant holds the key.
+            let chord_tones = [0, 4, 7, 10].map(|tone| (root + tone) % 12);
+            assert!(chord_tones.contains(&interval), "{mode:?} {interval}");
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use nih_plug::prelude::{Editor, ParamSetter};
+use nih_plug_egui::{create_egui_editor, egui, widgets, EguiState};
+
+use crate::{
+    chord_learn::ChordLearn,
+    diatonic,
+    params::{MidiTransposerParams, NoteParam, NOTE_NAMES},
+};
+
+/**
+ * The texts being edited.
+ */
+#[derive(Default)]
+struct Texts {
+    progression_chords: String,
+    key_map_chords: String,
+}
+
+pub(crate) fn default_state() -> Arc<EguiState> {
+    EguiState::from_size(320, 520)
+}
+
+pub(crate) fn create(
+    params: Arc<MidiTransposerParams>,
+    chord_learn: Arc<ChordLearn>,
+) -> Option<Box<dyn Editor>> {
+    let build_params = params.clone();
+    create_egui_editor(
+        params.editor_state.clone(),
+        Texts::default(),
+        // The texts are edited in copies, so the audio thread only waits for the locks when they change.
+        // They are copied each time the editor opens, since the host can restore the state in between.
+        move |_, texts| {
+            texts.progression_chords = build_params.progression_chords.read().unwrap().clone();
+            texts.key_map_chords = build_params.key_map_chords.read().unwrap().clone();
+        },
+        move |egui_ctx, setter, texts| {
+            // The learned chords are written from the GUI context so the host records the changes.
+            chord_learn.apply(&params, setter);
+            diatonic::apply(&params, setter);
+
+            egui::CentralPanel::default().show(egui_ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.heading("Bank");
+                    ui.add(widgets::ParamSlider::for_param(&params.bank, setter));
+                    // The key-switches and program changes don't move the bank param.
+                    ui.label(format!(
+                        "Playing bank {}",
+                        params.active_bank.load(Ordering::Acquire) + 1
+                    ));
+
+                    ui.separator();
+                    ui.heading("Chord Learn");
+                    ui.add(widgets::ParamSlider::for_param(&params.learn, setter));
+                    ui.label(if params.learn.value() {
+                        "Play a chord, it's learned when all the keys are released."
+                    } else {
+                        "Arm the chord learn to map the next chord played to its lowest note."
+                    });
+
+                    ui.separator();
+                    ui.heading("Progression");
+                    ui.add(widgets::ParamSlider::for_param(
+                        &params.progression.activated,
+                        setter,
+                    ));
+                    if ui
+                        .text_edit_singleline(&mut texts.progression_chords)
+                        .changed()
+                    {
+                        *params.progression_chords.write().unwrap() =
+                            texts.progression_chords.clone();
+                    }
+                    ui.label("The chord symbols to step through, like \"Am7 D9 Gmaj7 Cmaj7\".");
+
+                    ui.separator();
+                    ui.heading("Absolute Key Map");
+                    ui.add(widgets::ParamSlider::for_param(&params.key_map, setter));
+                    if ui.text_edit_singleline(&mut texts.key_map_chords).changed() {
+                        *params.key_map_chords.write().unwrap() = texts.key_map_chords.clone();
+                    }
+                    ui.label("The chords of single keys, like \"C2=Am7 C#2=D9 C3=Fmaj7/C\".");
+
+                    ui.separator();
+                    ui.heading("Diatonic Fill");
+                    ui.add(widgets::ParamSlider::for_param(
+                        &params.diatonic.key,
+                        setter,
+                    ));
+                    ui.add(widgets::ParamSlider::for_param(
+                        &params.diatonic.mode,
+                        setter,
+                    ));
+                    ui.add(widgets::ParamSlider::for_param(
+                        &params.diatonic.size,
+                        setter,
+                    ));
+                    ui.add(widgets::ParamSlider::for_param(
+                        &params.diatonic.out_of_scale,
+                        setter,
+                    ));
+                    ui.add(widgets::ParamSlider::for_param(
+                        &params.diatonic.fill,
+                        setter,
+                    ));
+                    ui.label(
+                    "Overwrites the note mapping of the playing bank with the chords of the key.",
+                );
+
+                    ui.separator();
+                    ui.heading("Note Mapping");
+                    ui.label(
+                        "The note panels of the playing bank, the hosts don't list the banks.",
+                    );
+                    for (note_name, note_param) in NOTE_NAMES.iter().zip(params.bank_notes()) {
+                        ui.collapsing(*note_name, |ui| note_panel(ui, note_param, setter));
+                    }
+                });
+            });
+        },
+    )
+}
+
+/**
+ * The sliders of every param of a note panel.
+ */
+fn note_panel(ui: &mut egui::Ui, note_param: &NoteParam, setter: &ParamSetter) {
+    ui.add(widgets::ParamSlider::for_param(&note_param.active, setter));
+    ui.add(widgets::ParamSlider::for_param(
+        &note_param.transpose,
+        setter,
+    ));
+    ui.add(widgets::ParamSlider::for_param(&note_param.octave, setter));
+    ui.add(widgets::ParamSlider::for_param(
+        &note_param.keep_root,
+        setter,
+    ));
+    ui.add(widgets::ParamSlider::for_param(
+        &note_param.root_velocity,
+        setter,
+    ));
+    ui.add(widgets::ParamSlider::for_param(
+        &note_param.root_channel,
+        setter,
+    ));
+    for (slot, interval) in note_param.intervals.iter().enumerate() {
+        ui.label(format!("Interval {}", slot + 1));
+        ui.add(widgets::ParamSlider::for_param(&interval.enabled, setter));
+        ui.add(widgets::ParamSlider::for_param(&interval.interval, setter));
+        ui.add(widgets::ParamSlider::for_param(&interval.velocity, setter));
+        ui.add(widgets::ParamSlider::for_param(&interval.channel, setter));
+        ui.add(widgets::ParamSlider::for_param(
+            &interval.probability,
+            setter,
+        ));
+    }
+    // The main intervals are the first layer.
+    for (layer, layer_param) in (2..).zip(&note_param.layers) {
+        ui.label(format!("Velocity layer {layer}"));
+        ui.add(widgets::ParamSlider::for_param(
+            &layer_param.activated,
+            setter,
+        ));
+        ui.add(widgets::ParamSlider::for_param(
+            &layer_param.min_velocity,
+            setter,
+        ));
+        for interval in &layer_param.intervals {
+            ui.add(widgets::ParamSlider::for_param(&interval.enabled, setter));
+            ui.add(widgets::ParamSlider::for_param(&interval.interval, setter));
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use nih_plug::prelude::Enum;
+
+use crate::{
+    chord_recognizer,
+    note_range::NoteRange,
+    params::{GestureParams, MidiTransposerParams},
+    Chord,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Gesture {
+    DoubleTap,
+    LongPress,
+}
+
+/**
+ * What a gesture does.
+ */
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum GestureAction {
+    #[name = "None"]
+    None,
+    /**
+     * Toggles the hold mode, on top of the chord hold param.
+     */
+    #[name = "Toggle latch"]
+    ToggleLatch,
+    /**
+     * Switches the sounding chord between its major and minor variants.
+     */
+    #[name = "Alternate chord"]
+    AlternateChord,
+    #[name = "Octave up"]
+    OctaveUp,
+    #[name = "Octave down"]
+    OctaveDown,
+    /**
+     * Toggles the arpeggiator, on top of the arp param.
+     */
+    #[name = "Toggle arp"]
+    ToggleArp,
+}
+
+/**
+ * Recognizes the double taps and long presses of the keys that play chords.
+ */
+pub(crate) struct GestureDetector {
+    pub(crate) sample_rate: f32,
+    /**
+     * The position in samples since the plugin started.
+     */
+    time: u64,
+    /**
+     * The last key pressed with the position of its note on, to recognize a double tap.
+     */
+    last_tap: Option<(u8, u64)>,
+    /**
+     * The key held with the position of its note on, until it becomes a long press.
+     */
+    press: Option<(u8, u64)>,
+}
+
+impl Default for GestureDetector {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44100.0,
+            time: 0,
+            last_tap: None,
+            press: None,
+        }
+    }
+}
+
+impl GestureDetector {
+    /**
+     * Returns a double tap if the key has been pressed again within the double tap window.
+     */
+    pub fn note_on(&mut self, params: &GestureParams, note: u8, timing: u32) -> Option<Gesture> {
+        let now = self.time + timing as u64;
+        self.press = Some((note, now));
+        let window = params.double_tap_window.value() / 1000.0 * self.sample_rate;
+        match self.last_tap {
+            Some((last_note, last_time))
+                if last_note == note && ((now - last_time) as f32) < window =>
+            {
+                // A third tap starts a new double tap.
+                self.last_tap = None;
+                Some(Gesture::DoubleTap)
+            }
+            _ => {
+                self.last_tap = Some((note, now));
+                None
+            }
+        }
+    }
+
+    pub fn note_off(&mut self, note: u8) {
+        if matches!(self.press, Some((pressed_note, _)) if pressed_note == note) {
+            self.press = None;
+        }
+    }
+
+    /**
+     * Moves the time forward at the end of a buffer,
+     * and returns a long press if the key held has reached the threshold.
+     */
+    pub fn advance(&mut self, params: &GestureParams, nb_samples: usize) -> Option<Gesture> {
+        self.time += nb_samples as u64;
+        let threshold = params.long_press_time.value() / 1000.0 * self.sample_rate;
+        match self.press {
+            Some((_, press_time)) if (self.time - press_time) as f32 >= threshold => {
+                self.press = None;
+                Some(Gesture::LongPress)
+            }
+            _ => None,
+        }
+    }
+}
+
+/**
+ * Returns the parallel minor of a major chord, or the parallel major of a minor chord,
+ * by moving its thirds by a semitone. The chords without a third are returned as they are.
+ */
+pub(crate) fn parallel_variant(chord: &Chord) -> Chord {
+    let Some(recognized) = chord_recognizer::recognize_chord(chord) else {
+        return *chord;
+    };
+    let (third, shift) = if recognized.intervals & (1 << 4) != 0 {
+        (4, -1)
+    } else if recognized.intervals & (1 << 3) != 0 {
+        (3, 1)
+    } else {
+        return *chord;
+    };
+    let third_class = (recognized.root + third) % 12;
+
+    let mut variant = Chord::default();
+    for note in (0..128).filter(|note| chord.contains(*note)) {
+        let new_note = if note % 12 == third_class {
+            (note as i32 + shift).clamp(0, 127) as u8
+        } else {
+            note
+        };
+        variant.add_voices(chord, note, new_note);
+    }
+    variant
+}
+
+/**
+ * Moves a chord by octaves, and fits it in the register.
+ */
+pub(crate) fn shift_octave(chord: &Chord, octaves: i32, params: &MidiTransposerParams) -> Chord {
+    if octaves == 0 {
+        return *chord;
+    }
+    chord.map_notes(&NoteRange::from_params(params), |note| note + 12 * octaves)
+}
@@ -0,0 +1,58 @@
+use nih_plug::prelude::Enum;
+
+use crate::{note_range::NoteRange, Chord};
+
+const MAJOR_SCALE: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+/**
+ * The modes of the major scale, in the order of the degree they start on.
+ */
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    #[name = "Ionian (major)"]
+    Ionian,
+    #[name = "Dorian"]
+    Dorian,
+    #[name = "Phrygian"]
+    Phrygian,
+    #[name = "Lydian"]
+    Lydian,
+    #[name = "Mixolydian"]
+    Mixolydian,
+    #[name = "Aeolian (minor)"]
+    Aeolian,
+    #[name = "Locrian"]
+    Locrian,
+}
+
+impl Mode {
+    /**
+     * The intervals of the 7 degrees of the mode from its tonic.
+     */
+    pub fn scale(self) -> [i32; 7] {
+        let start = self.to_index();
+        core::array::from_fn(|degree| {
+            (MAJOR_SCALE[(degree + start) % 7] - MAJOR_SCALE[start]).rem_euclid(12)
+        })
+    }
+}
+
+/**
+ * Re-spells a chord written in the major key of the tonic into another mode of the same tonic.
+ * Each note on a degree of the major scale moves to the same degree of the mode, so C major becomes
+ * C minor in C Dorian. The notes out of the scale are kept.
+ */
+pub(crate) fn respell(chord: &Chord, tonic: i32, mode: Mode, range: &NoteRange) -> Chord {
+    if mode == Mode::Ionian {
+        return *chord;
+    }
+    let scale = mode.scale();
+    chord.map_notes(range, |note| {
+        let interval = (note - tonic).rem_euclid(12);
+        let shift = MAJOR_SCALE
+            .iter()
+            .position(|degree| *degree == interval)
+            .map_or(0, |degree| scale[degree] - MAJOR_SCALE[degree]);
+        note + shift
+    })
+}
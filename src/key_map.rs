@@ -0,0 +1,66 @@
+use crate::chord_symbol::{self, ChordSymbol};
+
+/**
+ * Returns the chord symbol given to a key in the absolute key map.
+ * The key map is a list of entries separated by spaces, like "C2=Am7 C#2=D9 38=Fmaj7/C",
+ * where the keys are note names with their octave or note numbers.
+ * The invalid entries are skipped.
+ */
+pub(crate) fn find(key_map: &str, note: u8) -> Option<ChordSymbol> {
+    key_map
+        .split_whitespace()
+        .filter_map(|entry| {
+            let (key, symbol) = entry.split_once('=')?;
+            Some((parse_key(key)?, chord_symbol::parse(symbol)?))
+        })
+        .find(|(key, _)| *key == note)
+        .map(|(_, symbol)| symbol)
+}
+
+/**
+ * Parses a key written as a note number, or as a note name where C4 is the middle C (60).
+ */
+fn parse_key(key: &str) -> Option<u8> {
+    let note = match key.parse::<i32>() {
+        Ok(note) => note,
+        Err(_) => {
+            let (pitch_class, octave) = chord_symbol::parse_note(key)?;
+            (octave.parse::<i32>().ok()? + 1) * 12 + pitch_class as i32
+        }
+    };
+    u8::try_from(note).ok().filter(|note| *note < 128)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_MAP: &str = "C2=Am7 C#2=D9 38=Fmaj7/C C-1=E";
+
+    #[test]
+    fn finds_the_keys_by_name_or_number() {
+        assert_eq!(find(KEY_MAP, 36).map(|symbol| symbol.root), Some(9));
+        assert_eq!(find(KEY_MAP, 37).map(|symbol| symbol.root), Some(2));
+        let symbol = find(KEY_MAP, 38).unwrap();
+        assert_eq!((symbol.root, symbol.bass), (5, Some(0)));
+        assert_eq!(find(KEY_MAP, 0).map(|symbol| symbol.root), Some(4));
+    }
+
+    #[test]
+    fn the_other_keys_are_not_mapped() {
+        assert_eq!(find(KEY_MAP, 48), None);
+        assert_eq!(find("", 36), None);
+    }
+
+    #[test]
+    fn skips_the_invalid_entries() {
+        assert_eq!(find("C2=Hm7 C2=Am", 36).map(|symbol| symbol.root), Some(9));
+        assert_eq!(find("C2 200=C X4=C", 36), None);
+    }
+
+    #[test]
+    fn the_keys_stop_at_the_top_of_the_midi_range() {
+        assert!(find("G9=C", 127).is_some());
+        assert_eq!(find("G#9=C 128=C", 127), None);
+    }
+}
@@ -1,18 +1,56 @@
 use arp_processor::ArpProcessor;
+use bass_processor::BassProcessor;
+use chord_learn::ChordLearn;
 use chord_processor::ChordProcessor;
+use debounce::Debouncer;
+use gesture::{Gesture, GestureAction, GestureDetector};
 use nih_plug::prelude::*;
 use note_info::NoteInfo;
-use params::MidiTransposerParams;
+use note_range::NoteRange;
+use params::{MidiTransposerParams, NB_BANKS};
+use progression::ProgressionStepper;
+use random::Random;
 use std::sync::{atomic::AtomicBool, Arc};
+use zone::ZoneMode;
 
 mod arp_processor;
+mod bass_processor;
+mod chord_learn;
 mod chord_processor;
+mod chord_recognizer;
+mod chord_symbol;
+mod debounce;
+mod diatonic;
+mod editor;
+mod gesture;
+mod key;
+mod key_map;
 mod note_info;
+mod note_panel;
+mod note_range;
 mod params;
-
+mod pedal;
+mod progression;
+mod random;
+mod scheduler;
+mod strum;
+mod substitution;
+mod transform;
+mod velocity;
+mod voicing;
+mod zone;
+
+/**
+ * The channel mode messages sent by the panic buttons.
+ */
+const ALL_SOUND_OFF: u8 = 120;
+const ALL_NOTES_OFF: u8 = 123;
+
+#[derive(Clone, Copy, PartialEq)]
 enum ProcessorType {
     Chord,
     Arpeggio,
+    Bass,
 }
 
 #[derive(Debug)]
@@ -24,7 +62,118 @@ enum NoteTrigger {
 #[derive(Clone, Copy, Debug)]
 struct Chord {
     pub notes: u128,
-    pub channel: u8,
+    /**
+     * The output channels of each note as a mask, so the notes of a chord can be routed to different instruments.
+     * The same note can be played on several channels.
+     */
+    pub channels: [u16; 128],
+    /**
+     * The velocity of each note on each channel.
+     */
+    pub velocities: [[f32; 16]; 128],
+}
+
+impl Default for Chord {
+    fn default() -> Self {
+        Self {
+            notes: 0b0,
+            channels: [0; 128],
+            velocities: [[0.0; 16]; 128],
+        }
+    }
+}
+
+impl Chord {
+    pub fn contains(&self, note: u8) -> bool {
+        self.notes & (1 << note) != 0
+    }
+
+    /**
+     * Adds a note on a channel. If the note is already played on this channel, the first one is kept,
+     * so the root added first keeps its own velocity when an interval lands on it.
+     */
+    pub fn add_note(&mut self, note: u8, velocity: f32, channel: u8) {
+        let channel = channel & 15;
+        if self.channels[note as usize] & (1 << channel) == 0 {
+            self.channels[note as usize] |= 1 << channel;
+            self.velocities[note as usize][channel as usize] = velocity;
+        }
+        self.notes |= 1 << note;
+    }
+
+    /**
+     * Adds a note of another chord on all its channels, possibly as another note.
+     */
+    pub fn add_voices(&mut self, chord: &Chord, note: u8, new_note: u8) {
+        for (channel, velocity) in chord.voices(note) {
+            self.add_note(new_note, velocity, channel);
+        }
+    }
+
+    /**
+     * Returns the notes from the lowest to the highest and how many there are, without allocating.
+     */
+    pub fn sorted_notes(&self) -> ([u8; 128], usize) {
+        let mut notes = [0u8; 128];
+        let mut count = 0;
+        for note in (0..128).filter(|note| self.contains(*note)) {
+            notes[count] = note;
+            count += 1;
+        }
+        (notes, count)
+    }
+
+    /**
+     * Moves every note of the chord, and fits it in the register.
+     * The velocity and channel of each note follow it.
+     */
+    pub fn map_notes(&self, range: &NoteRange, map_note: impl Fn(i32) -> i32) -> Chord {
+        let mut mapped = Chord::default();
+        for note in (0..128).filter(|note| self.contains(*note)) {
+            if let Some(new_note) = range.fit(map_note(note as i32)) {
+                mapped.add_voices(self, note, new_note);
+            }
+        }
+        mapped
+    }
+
+    pub fn remove_note(&mut self, note: u8) {
+        self.notes &= !(1 << note);
+        self.channels[note as usize] = 0;
+    }
+
+    /**
+     * Removes a note from some channels only, the note is removed when it's left on no channel.
+     */
+    pub fn remove_channels(&mut self, note: u8, channels: u16) {
+        self.channels[note as usize] &= !channels;
+        if self.channels[note as usize] == 0 {
+            self.notes &= !(1 << note);
+        }
+    }
+
+    /**
+     * The channels a note is played on, with its velocity on each one.
+     */
+    pub fn voices(&self, note: u8) -> impl Iterator<Item = (u8, f32)> + '_ {
+        let channels = if self.contains(note) {
+            self.channels[note as usize]
+        } else {
+            0
+        };
+        (0..16u8)
+            .filter(move |channel| channels & (1 << channel) != 0)
+            .map(move |channel| (channel, self.velocities[note as usize][channel as usize]))
+    }
+
+    /**
+     * The velocity of the loudest channel a note is played on.
+     */
+    pub fn velocity(&self, note: u8) -> f32 {
+        self.voices(note)
+            .map(|(_, velocity)| velocity)
+            .fold(0.0, f32::max)
+    }
 }
 
 #[derive(Debug)]
@@ -34,6 +183,15 @@ struct NotesState {
     pub current_note_held: Option<NoteInfo>,
     pub current_chord: Option<Chord>,
     pub previous_chord: Option<Chord>,
+    /**
+     * Whether the sustain and sostenuto pedals are down on the input channel.
+     */
+    pub sustain: bool,
+    pub sostenuto: bool,
+    /**
+     * The timing of the last pedal change in the buffer.
+     */
+    pub pedal_timing: u32,
 }
 
 impl Default for NotesState {
@@ -44,6 +202,9 @@ impl Default for NotesState {
             current_note_held: None,
             current_chord: None,
             previous_chord: None,
+            sustain: false,
+            sostenuto: false,
+            pedal_timing: 0,
         }
     }
 }
@@ -55,10 +216,13 @@ pub(crate) trait MidiProcessor {
         notes_state: &NotesState,
         nb_samples: usize,
     ) -> ProcessStatus;
-    fn arp_toggled(
+    /**
+     * Called when the processor becomes the active one, or when another one takes over.
+     */
+    fn toggled(
         &mut self,
         context: &mut impl ProcessContext<MidiTransposer>,
-        on_off: bool,
+        active: bool,
         notes_state: &NotesState,
     );
 }
@@ -82,27 +246,131 @@ struct MidiTransposer {
     arp_processor: ArpProcessor,
 
     /**
-     * Will be set by the param callback to know at the beginning of the process if the arp should be reset
+     * The chord to bass processor
      */
-    should_reset_arp: Arc<AtomicBool>,
+    bass_processor: BassProcessor,
+
+    /**
+     * Will be set by the param callbacks to know at the beginning of the process if the active processor should be updated
+     */
+    should_update_processor: Arc<AtomicBool>,
 
     /**
      * The state of the notes played
      */
     notes_state: NotesState,
+
+    /**
+     * The chords captured in learn mode, to be written in the params by the editor
+     */
+    chord_learn: Arc<ChordLearn>,
+
+    /**
+     * The keys pressed since the learn mode was armed, and the ones that are still held
+     */
+    learned_notes: u128,
+    learn_notes_held: u128,
+
+    /**
+     * The key of the chord kept sounding by the hold mode after its release
+     */
+    latched_note: Option<u8>,
+
+    /**
+     * The keys that didn't play anything, like the ones pressed to release a held chord or the key-switches,
+     * so their note offs are ignored
+     */
+    ignored_keys: u128,
+
+    /**
+     * Draws the random intervals and variations of the chords
+     */
+    random: Random,
+
+    /**
+     * The position in the chord progression
+     */
+    progression_stepper: ProgressionStepper,
+
+    /**
+     * Filters the bounces and ghost notes of the input
+     */
+    debouncer: Debouncer,
+
+    /**
+     * Recognizes the double taps and long presses
+     */
+    gestures: GestureDetector,
+
+    /**
+     * The state changed by the gestures, on top of the params
+     */
+    latch_toggled: bool,
+    arp_toggled: bool,
+    octave_shift: i32,
+
+    /**
+     * The CCs held on the input channel, for the substitution rules
+     */
+    ccs_held: u128,
+
+    /**
+     * The pedals passed through while they are down, so their release is passed through too
+     */
+    pedals_passed_through: u128,
 }
 
 impl MidiTransposer {
     fn process_note_on(&mut self, note_info: &NoteInfo) {
+        let gesture =
+            self.gestures
+                .note_on(&self.params.gestures, note_info.note, note_info.timing);
+
+        // Pressing the key of a held chord again releases it.
+        if self.latched_note == Some(note_info.note) {
+            self.release_hold();
+            self.ignored_keys |= 1 << note_info.note;
+            // The note off of the key is ignored, so the press is over for the gestures too.
+            self.gestures.note_off(note_info.note);
+            if let Some(gesture) = gesture {
+                self.run_gesture(gesture);
+            }
+            return;
+        }
+        // Otherwise the held chord is stopped as the previous chord.
+        self.latched_note = None;
+
         self.notes_state.notes_held.push(*note_info);
         self.notes_state.previous_chord = self.notes_state.current_chord;
-        self.notes_state.current_chord =
-            Some(ChordProcessor::build_chord(self.params.clone(), note_info));
+        let chord = match self.progression_chord(note_info) {
+            Some(chord) => chord,
+            None => self.build_chord(note_info),
+        };
+        self.notes_state.current_chord = Some(self.finish_chord(&chord));
         self.notes_state.current_note_held = Some(*note_info);
         self.notes_state.trigger = Some(NoteTrigger::Play);
+
+        if let Some(gesture) = gesture {
+            self.run_gesture(gesture);
+        }
     }
 
     fn process_note_off(&mut self, note_info: &NoteInfo) {
+        self.gestures.note_off(note_info.note);
+
+        // In hold mode, releasing the last key keeps its chord sounding.
+        if self.hold_active()
+            && self
+                .notes_state
+                .notes_held
+                .iter()
+                .all(|n| n.note == note_info.note)
+        {
+            self.notes_state.notes_held.clear();
+            self.latched_note = Some(note_info.note);
+            return;
+        }
+
         self.notes_state.previous_chord = self.notes_state.current_chord;
 
         // Remove the pressed key from the list of held notes.
@@ -116,40 +384,264 @@ impl MidiTransposer {
             self.notes_state.trigger = Some(NoteTrigger::Stop);
         } else {
             self.notes_state.current_note_held = Some(*self.notes_state.notes_held.last().unwrap());
-            self.notes_state.current_chord = Some(ChordProcessor::build_chord(
-                self.params.clone(),
-                self.notes_state.current_note_held.as_ref().unwrap(),
-            ));
+            // The chord of the progression keeps playing while a key is held.
+            if self.params.progression.activated.value() {
+                return;
+            }
+            let chord = self.build_chord(&self.notes_state.current_note_held.unwrap());
+            self.notes_state.current_chord = Some(self.finish_chord(&chord));
             self.notes_state.trigger = Some(NoteTrigger::Play);
         }
     }
 
+    /**
+     * Builds the chord of a key from the absolute key map if it's in it, otherwise from its pitch class.
+     * The key map is skipped while it's being edited.
+     */
+    fn build_chord(&mut self, note_info: &NoteInfo) -> Chord {
+        if self.params.key_map.value() {
+            let symbol = self
+                .params
+                .key_map_chords
+                .try_read()
+                .ok()
+                .and_then(|key_map_chords| key_map::find(&key_map_chords, note_info.note));
+            if let Some(symbol) = symbol {
+                // The root is in the octave of the key.
+                let root = (note_info.note - note_info.note % 12 + symbol.root) as i32;
+                return chord_symbol::build_chord(
+                    &self.params,
+                    &symbol,
+                    root,
+                    note_info,
+                    &mut self.random,
+                );
+            }
+        }
+        ChordProcessor::build_chord(self.params.clone(), note_info, &mut self.random)
+    }
+
+    /**
+     * Returns the chord of the progression if the key steps through it.
+     * The key is mapped as usual if the progression is empty, or if it's being edited.
+     */
+    fn progression_chord(&mut self, note_info: &NoteInfo) -> Option<Chord> {
+        if !self.params.progression.activated.value() {
+            return None;
+        }
+        let step = progression::key_step(&self.params.progression, note_info.note)?;
+        let progression_chords = self.params.progression_chords.try_read().ok()?;
+        let symbol = self.progression_stepper.step(&progression_chords, step)?;
+        let root = (self.params.progression.octave.value() + 1) * 12 + symbol.root as i32;
+        Some(chord_symbol::build_chord(
+            &self.params,
+            &symbol,
+            root,
+            note_info,
+            &mut self.random,
+        ))
+    }
+
+    /**
+     * Applies the transform, the substitution rules and the octave shift of the gestures
+     * to a chord that has been built.
+     */
+    fn finish_chord(&mut self, chord: &Chord) -> Chord {
+        let chord = transform::apply(chord, &self.params);
+        let chord = substitution::apply(&chord, &self.params, self.ccs_held, &mut self.random);
+        gesture::shift_octave(&chord, self.octave_shift, &self.params)
+    }
+
+    /**
+     * Plays another chord instead of the current one.
+     * If the current chord has not been played yet, it's just replaced.
+     */
+    fn replace_current_chord(&mut self, chord: Chord) {
+        if !matches!(self.notes_state.trigger, Some(NoteTrigger::Play)) {
+            self.notes_state.previous_chord = self.notes_state.current_chord;
+        }
+        self.notes_state.current_chord = Some(chord);
+        self.notes_state.trigger = Some(NoteTrigger::Play);
+    }
+
+    fn run_gesture(&mut self, gesture: Gesture) {
+        let action = match gesture {
+            Gesture::DoubleTap => self.params.gestures.double_tap_action.value(),
+            Gesture::LongPress => self.params.gestures.long_press_action.value(),
+        };
+        nih_trace!("MidiTransposer::run_gesture -> {gesture:?} runs {action:?}");
+        match action {
+            GestureAction::None => {}
+            GestureAction::ToggleLatch => self.latch_toggled = !self.latch_toggled,
+            GestureAction::AlternateChord => {
+                if let Some(chord) = self.notes_state.current_chord {
+                    self.replace_current_chord(gesture::parallel_variant(&chord));
+                }
+            }
+            GestureAction::OctaveUp | GestureAction::OctaveDown => {
+                let shift = if action == GestureAction::OctaveUp {
+                    1
+                } else {
+                    -1
+                };
+                let octave_shift = (self.octave_shift + shift).clamp(-2, 2);
+                // The sounding chord moves too.
+                if let Some(chord) = self.notes_state.current_chord {
+                    let octaves = octave_shift - self.octave_shift;
+                    self.replace_current_chord(gesture::shift_octave(
+                        &chord,
+                        octaves,
+                        &self.params,
+                    ));
+                }
+                self.octave_shift = octave_shift;
+            }
+            GestureAction::ToggleArp => {
+                self.arp_toggled = !self.arp_toggled;
+                self.should_update_processor
+                    .store(true, std::sync::atomic::Ordering::Release);
+            }
+        }
+    }
+
+    /**
+     * The hold mode follows the param, and it's toggled by the latch gesture.
+     */
+    fn hold_active(&self) -> bool {
+        self.params.hold.value() != self.latch_toggled
+    }
+
+    /**
+     * Stops the chord kept sounding by the hold mode, if there's one.
+     */
+    fn release_hold(&mut self) {
+        if self.latched_note.take().is_some() {
+            self.notes_state.previous_chord = self.notes_state.current_chord;
+            self.notes_state.current_note_held = None;
+            self.notes_state.current_chord = None;
+            self.notes_state.trigger = Some(NoteTrigger::Stop);
+        }
+    }
+
+    /**
+     * While learning, the notes played are sent as they are,
+     * and the chord is captured once all its keys are released.
+     */
+    fn process_learn_event(
+        &mut self,
+        context: &mut impl ProcessContext<MidiTransposer>,
+        note_info: &NoteInfo,
+        note_on: bool,
+    ) {
+        send_note_event(context, note_info, note_on);
+        if note_on {
+            self.learned_notes |= 1 << note_info.note;
+            self.learn_notes_held |= 1 << note_info.note;
+        } else {
+            self.learn_notes_held &= !(1 << note_info.note);
+            if self.learn_notes_held == 0 {
+                self.chord_learn.capture(self.learned_notes);
+                self.learned_notes = 0;
+            }
+        }
+    }
+
     fn update_processor(&mut self, context: &mut impl ProcessContext<MidiTransposer>) {
-        let arp_activated = self.params.arp.activated.value();
-        self.processor_type = if arp_activated {
+        let processor_type = if self.params.bass.activated.value() {
+            ProcessorType::Bass
+        } else if self.params.arp.activated.value() != self.arp_toggled {
             ProcessorType::Arpeggio
         } else {
             ProcessorType::Chord
         };
-        self.chord_processor
-            .arp_toggled(context, arp_activated, &self.notes_state);
-        self.arp_processor
-            .arp_toggled(context, arp_activated, &self.notes_state);
+        if processor_type == self.processor_type {
+            return;
+        }
+
+        // Let the previous processor stop its notes before the new one takes over.
+        self.toggle_processor(context, self.processor_type, false);
+        self.toggle_processor(context, processor_type, true);
+        self.processor_type = processor_type;
+    }
+
+    fn toggle_processor(
+        &mut self,
+        context: &mut impl ProcessContext<MidiTransposer>,
+        processor_type: ProcessorType,
+        active: bool,
+    ) {
+        match processor_type {
+            ProcessorType::Chord => {
+                self.chord_processor
+                    .toggled(context, active, &self.notes_state)
+            }
+            ProcessorType::Arpeggio => {
+                self.arp_processor
+                    .toggled(context, active, &self.notes_state)
+            }
+            ProcessorType::Bass => self
+                .bass_processor
+                .toggled(context, active, &self.notes_state),
+        }
+    }
+}
+
+/**
+ * Sends a note played on the input as it is, on the output channel of the note info.
+ */
+fn send_note_event(
+    context: &mut impl ProcessContext<MidiTransposer>,
+    note_info: &NoteInfo,
+    note_on: bool,
+) {
+    if note_on {
+        context.send_event(NoteEvent::NoteOn {
+            note: note_info.note,
+            channel: note_info.channel,
+            velocity: note_info.velocity,
+            voice_id: None,
+            timing: note_info.timing,
+        });
+    } else {
+        context.send_event(NoteEvent::NoteOff {
+            note: note_info.note,
+            channel: note_info.channel,
+            velocity: 0.0,
+            voice_id: None,
+            timing: note_info.timing,
+        });
     }
 }
 
 impl Default for MidiTransposer {
     fn default() -> Self {
-        let should_reset_arp = Arc::new(AtomicBool::new(true));
-        let params = Arc::new(MidiTransposerParams::new(should_reset_arp.clone()));
+        let should_update_processor = Arc::new(AtomicBool::new(true));
+        let params = Arc::new(MidiTransposerParams::new(should_update_processor.clone()));
+        let chord_processor = ChordProcessor::new(Arc::clone(&params.chord));
         let arp_processor = ArpProcessor::new(Arc::clone(&params.arp));
+        let bass_processor = BassProcessor::new(Arc::clone(&params.bass));
         Self {
             params,
             processor_type: ProcessorType::Chord,
-            chord_processor: ChordProcessor::default(),
+            chord_processor,
             arp_processor,
-            should_reset_arp,
+            bass_processor,
+            should_update_processor,
             notes_state: NotesState::default(),
+            chord_learn: Arc::new(ChordLearn::default()),
+            learned_notes: 0,
+            learn_notes_held: 0,
+            latched_note: None,
+            ignored_keys: 0,
+            random: Random::new(1),
+            progression_stepper: ProgressionStepper::default(),
+            debouncer: Debouncer::default(),
+            gestures: GestureDetector::default(),
+            latch_toggled: false,
+            arp_toggled: false,
+            octave_shift: 0,
+            ccs_held: 0,
+            pedals_passed_through: 0,
         }
     }
 }
@@ -165,8 +657,8 @@ impl Plugin for MidiTransposer {
     // This plugin doesn't have any audio IO
     const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[];
 
-    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
-    const MIDI_OUTPUT: MidiConfig = MidiConfig::Basic;
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
 
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
@@ -177,6 +669,10 @@ impl Plugin for MidiTransposer {
         self.params.clone()
     }
 
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone(), self.chord_learn.clone())
+    }
+
     fn initialize(
         &mut self,
         _audio_io_layout: &AudioIOLayout,
@@ -184,10 +680,20 @@ impl Plugin for MidiTransposer {
         _context: &mut impl InitContext<Self>,
     ) -> bool {
         nih_trace!("Initializing MidiTransposer");
+        self.chord_processor.sample_rate = buffer_config.sample_rate;
         self.arp_processor.sample_rate = buffer_config.sample_rate;
+        self.debouncer.sample_rate = buffer_config.sample_rate;
+        self.gestures.sample_rate = buffer_config.sample_rate;
         true
     }
 
+    fn reset(&mut self) {
+        // The random draws start over from the seed, so the renders are deterministic.
+        let seed = self.params.seed.value() as u64;
+        self.random = Random::new(seed);
+        self.chord_processor.reset(seed);
+    }
+
     fn process(
         &mut self,
         buffer: &mut Buffer,
@@ -196,10 +702,11 @@ impl Plugin for MidiTransposer {
     ) -> ProcessStatus {
         // Reset the note trigger for the processors.
         self.notes_state.trigger = None;
+        self.notes_state.pedal_timing = 0;
 
-        // Check if the arpeggiator has been turned on/off to reset it and notify the processors.
+        // Check if the arpeggiator or the chord to bass mode has been turned on/off to notify the processors.
         if self
-            .should_reset_arp
+            .should_update_processor
             .compare_exchange(
                 true,
                 false,
@@ -211,6 +718,11 @@ impl Plugin for MidiTransposer {
             self.update_processor(context);
         }
 
+        // Turning the hold mode off releases the chord it was keeping.
+        if !self.hold_active() {
+            self.release_hold();
+        }
+
         // Process the incoming events.
         while let Some(event) = context.next_event() {
             // Exclude notes that are not from the filtered channel
@@ -222,19 +734,6 @@ impl Plugin for MidiTransposer {
                 continue;
             }
 
-            // The output channel will be the same as the input channel if the output channel param is set to 0
-            // Otherwise, it will be the value of the output channel param - 1 (because channels go from 0 to 15)
-            let output_channel = match self.params.out_channel.value() {
-                0 => {
-                    if let Some(channel) = event.channel() {
-                        channel
-                    } else {
-                        0
-                    }
-                }
-                _ => self.params.out_channel.value() as u8 - 1,
-            };
-
             match event {
                 NoteEvent::NoteOn {
                     note,
@@ -248,16 +747,124 @@ impl Plugin for MidiTransposer {
                     velocity,
                     ..
                 } => {
+                    // Each zone of the keyboard has its own output channel.
+                    let zone = self.params.zone(note);
+                    let output_channel = zone.output_channel(event.channel());
                     let note_info = NoteInfo::new(note, output_channel, velocity, timing);
+                    let note_on = matches!(event, NoteEvent::NoteOn { .. });
+
+                    // The bounces and ghost notes are dropped with their note off,
+                    // unless the key is still held from an accepted note on.
+                    if !note_on {
+                        self.debouncer.note_off(note, timing);
+                    } else if !self.debouncer.accept_note_on(
+                        &self.params.debounce,
+                        note,
+                        velocity,
+                        timing,
+                    ) {
+                        if !self.debouncer.is_held(note) {
+                            self.ignored_keys |= 1 << note;
+                        }
+                        continue;
+                    }
+
+                    if !note_on && self.ignored_keys & (1 << note) != 0 {
+                        self.ignored_keys &= !(1 << note);
+                        continue;
+                    }
+
+                    // The key-switches select the bank of note maps. The chords playing were built
+                    // with the previous bank, so they are released as they were played.
+                    if let (true, Some(bank)) = (note_on, self.params.keyswitch_bank(note)) {
+                        self.params.select_bank(bank);
+                        self.ignored_keys |= 1 << note;
+                        continue;
+                    }
+
+                    // The keys pressed in learn mode are released in learn mode too,
+                    // even if it has been disarmed in the meantime.
+                    let learning = if note_on {
+                        self.params.learn.value()
+                    } else {
+                        self.learn_notes_held & (1 << note) != 0
+                    };
+                    if learning {
+                        self.process_learn_event(context, &note_info, note_on);
+                        continue;
+                    }
+
+                    // The keys of a pass-through zone don't build chords. A note off is passed through
+                    // when its key was not held for a chord, so the zones can change while playing.
+                    let pass_through = if note_on {
+                        zone.mode == ZoneMode::PassThrough
+                    } else {
+                        !self.notes_state.notes_held.iter().any(|n| n.note == note)
+                    };
+                    if pass_through {
+                        send_note_event(context, &note_info, note_on);
+                        continue;
+                    }
                     match event {
                         NoteEvent::NoteOn { .. } => self.process_note_on(&note_info),
                         NoteEvent::NoteOff { .. } => self.process_note_off(&note_info),
                         _ => context.send_event(event),
                     }
                 }
+                NoteEvent::MidiProgramChange { program, .. } if (program as usize) < NB_BANKS => {
+                    self.params.select_bank(program as usize);
+                }
+                // All notes off and all sound off also stop the held chord.
+                NoteEvent::MidiCC { cc, .. } if cc == ALL_SOUND_OFF || cc == ALL_NOTES_OFF => {
+                    self.release_hold();
+                    context.send_event(event);
+                }
+                // The chord processor holds the chords back with the pedals instead of passing them through,
+                // since the chords can be sent on other channels than the input. The other processors
+                // don't handle them, so they are passed through until they are released.
+                NoteEvent::MidiCC {
+                    cc: cc @ (pedal::SUSTAIN_PEDAL | pedal::SOSTENUTO_PEDAL),
+                    value,
+                    timing,
+                    ..
+                } => {
+                    let down = value >= 0.5;
+                    if cc == pedal::SUSTAIN_PEDAL {
+                        self.notes_state.sustain = down;
+                    } else {
+                        self.notes_state.sostenuto = down;
+                    }
+                    self.notes_state.pedal_timing = timing;
+
+                    if self.processor_type != ProcessorType::Chord
+                        || self.pedals_passed_through & (1 << cc) != 0
+                    {
+                        context.send_event(event);
+                        if down {
+                            self.pedals_passed_through |= 1 << cc;
+                        } else {
+                            self.pedals_passed_through &= !(1 << cc);
+                        }
+                    }
+                }
+                NoteEvent::MidiCC { cc, value, .. } => {
+                    if value >= 0.5 {
+                        self.ccs_held |= 1 << cc;
+                    } else {
+                        self.ccs_held &= !(1 << cc);
+                    }
+                    context.send_event(event);
+                }
                 _ => context.send_event(event),
             }
         }
+        self.debouncer.advance(buffer.samples());
+        if let Some(gesture) = self
+            .gestures
+            .advance(&self.params.gestures, buffer.samples())
+        {
+            self.run_gesture(gesture);
+        }
 
         match self.processor_type {
             ProcessorType::Chord => {
@@ -268,6 +875,10 @@ impl Plugin for MidiTransposer {
                 self.arp_processor
                     .process(context, &self.notes_state, buffer.samples())
             }
+            ProcessorType::Bass => {
+                self.bass_processor
+                    .process(context, &self.notes_state, buffer.samples())
+            }
         }
     }
 }
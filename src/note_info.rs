@@ -15,13 +15,4 @@ impl NoteInfo {
             timing,
         }
     }
-
-    pub fn with_transposition(&self, transposition: i8) -> Self {
-        Self {
-            note: (self.note as i8 + transposition) as u8,
-            channel: self.channel,
-            velocity: self.velocity,
-            timing: self.timing,
-        }
-    }
 }
@@ -0,0 +1,157 @@
+use nih_plug::prelude::{BoolParam, FloatParam, IntParam, Param, ParamSetter};
+
+use crate::{
+    chord_learn::set_param,
+    params::{NoteParam, NB_INTERVALS, NB_VELOCITY_LAYERS},
+};
+
+/**
+ * The values of a note panel, read from its params or computed before they are written to them,
+ * so the chords are always built from the same values.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct NotePanel {
+    pub active: bool,
+    pub transpose: i32,
+    pub octave: i32,
+    pub keep_root: bool,
+    pub root_velocity: f32,
+    pub root_channel: i32,
+    pub intervals: [PanelInterval; NB_INTERVALS],
+    pub layers: [PanelLayer; NB_VELOCITY_LAYERS],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PanelInterval {
+    pub enabled: bool,
+    pub interval: i32,
+    pub velocity: f32,
+    pub channel: i32,
+    pub probability: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PanelLayer {
+    pub activated: bool,
+    pub min_velocity: f32,
+    /**
+     * Whether each interval slot is enabled, with its interval.
+     */
+    pub intervals: [(bool, i32); NB_INTERVALS],
+}
+
+impl NotePanel {
+    /**
+     * The current values of the params of a note panel.
+     */
+    pub fn from_params(note_param: &NoteParam) -> Self {
+        Self::read(
+            note_param,
+            |param| param.value(),
+            |param| param.value(),
+            |param| param.value(),
+        )
+    }
+
+    /**
+     * The default values of the params of a note panel.
+     */
+    pub fn defaults(note_param: &NoteParam) -> Self {
+        Self::read(
+            note_param,
+            |param| param.default_plain_value(),
+            |param| param.default_plain_value(),
+            |param| param.default_plain_value(),
+        )
+    }
+
+    fn read(
+        note_param: &NoteParam,
+        bool_value: impl Fn(&BoolParam) -> bool,
+        int_value: impl Fn(&IntParam) -> i32,
+        float_value: impl Fn(&FloatParam) -> f32,
+    ) -> Self {
+        Self {
+            active: bool_value(&note_param.active),
+            transpose: int_value(&note_param.transpose),
+            octave: int_value(&note_param.octave),
+            keep_root: bool_value(&note_param.keep_root),
+            root_velocity: float_value(&note_param.root_velocity),
+            root_channel: int_value(&note_param.root_channel),
+            intervals: core::array::from_fn(|slot| {
+                let interval_param = &note_param.intervals[slot];
+                PanelInterval {
+                    enabled: bool_value(&interval_param.enabled),
+                    interval: int_value(&interval_param.interval),
+                    velocity: float_value(&interval_param.velocity),
+                    channel: int_value(&interval_param.channel),
+                    probability: float_value(&interval_param.probability),
+                }
+            }),
+            layers: core::array::from_fn(|layer| {
+                let layer_param = &note_param.layers[layer];
+                PanelLayer {
+                    activated: bool_value(&layer_param.activated),
+                    min_velocity: float_value(&layer_param.min_velocity),
+                    intervals: core::array::from_fn(|slot| {
+                        (
+                            bool_value(&layer_param.intervals[slot].enabled),
+                            int_value(&layer_param.intervals[slot].interval),
+                        )
+                    }),
+                }
+            }),
+        }
+    }
+
+    /**
+     * Writes the values into the params of a note panel, from the editor.
+     */
+    pub fn write(&self, note_param: &NoteParam, setter: &ParamSetter) {
+        set_param(setter, &note_param.active, self.active);
+        set_param(setter, &note_param.transpose, self.transpose);
+        set_param(setter, &note_param.octave, self.octave);
+        set_param(setter, &note_param.keep_root, self.keep_root);
+        set_param(setter, &note_param.root_velocity, self.root_velocity);
+        set_param(setter, &note_param.root_channel, self.root_channel);
+        for (interval, interval_param) in self.intervals.iter().zip(&note_param.intervals) {
+            set_param(setter, &interval_param.enabled, interval.enabled);
+            set_param(setter, &interval_param.interval, interval.interval);
+            set_param(setter, &interval_param.velocity, interval.velocity);
+            set_param(setter, &interval_param.channel, interval.channel);
+            set_param(setter, &interval_param.probability, interval.probability);
+        }
+        for (layer, layer_param) in self.layers.iter().zip(&note_param.layers) {
+            set_param(setter, &layer_param.activated, layer.activated);
+            set_param(setter, &layer_param.min_velocity, layer.min_velocity);
+            for ((enabled, interval), interval_param) in
+                layer.intervals.iter().zip(&layer_param.intervals)
+            {
+                set_param(setter, &interval_param.enabled, *enabled);
+                set_param(setter, &interval_param.interval, *interval);
+            }
+        }
+    }
+
+    /**
+     * Returns the velocity layer to use for a note played at this velocity,
+     * the one with the highest threshold wins. None means the main intervals are used.
+     */
+    pub fn velocity_layer(&self, velocity: f32) -> Option<&PanelLayer> {
+        self.layers
+            .iter()
+            .filter(|layer| layer.activated && velocity >= layer.min_velocity)
+            .max_by(|a, b| a.min_velocity.total_cmp(&b.min_velocity))
+    }
+
+    /**
+     * Returns the interval of a slot if it's enabled, in the velocity layer or in the main intervals.
+     */
+    pub fn interval(&self, slot: usize, layer: Option<&PanelLayer>) -> Option<i32> {
+        let (enabled, interval) = match layer {
+            Some(layer) => layer.intervals[slot],
+            None => (self.intervals[slot].enabled, self.intervals[slot].interval),
+        };
+        enabled.then_some(interval)
+    }
+}
@@ -0,0 +1,141 @@
+use nih_plug::prelude::Enum;
+
+use crate::{params::MidiTransposerParams, Chord};
+
+/**
+ * What happens to the notes that fall outside of the configured register.
+ */
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum RangePolicy {
+    /**
+     * Move the note by octaves until it fits in the register.
+     */
+    #[name = "Fold"]
+    Fold,
+    /**
+     * Replace the note with the closest bound of the register.
+     */
+    #[name = "Clamp"]
+    Clamp,
+    #[name = "Drop"]
+    Drop,
+}
+
+/**
+ * The register the notes of a chord must fit in, with the policy to apply to the others.
+ */
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct NoteRange {
+    policy: RangePolicy,
+    min: i32,
+    max: i32,
+}
+
+impl NoteRange {
+    pub fn from_params(params: &MidiTransposerParams) -> Self {
+        let min = params.range_min.value().clamp(0, 127);
+        let max = params.range_max.value().clamp(0, 127);
+        Self {
+            policy: params.range_policy.value(),
+            min: min.min(max),
+            max: min.max(max),
+        }
+    }
+
+    /**
+     * Returns the note to play for a computed note, which can be anywhere outside of the midi range,
+     * or None if it must be dropped.
+     */
+    pub fn fit(&self, note: i32) -> Option<u8> {
+        let fitted = match self.policy {
+            _ if (self.min..=self.max).contains(&note) => note,
+            RangePolicy::Fold => {
+                let mut folded = note;
+                if folded < self.min {
+                    folded += (self.min - folded + 11) / 12 * 12;
+                } else {
+                    folded -= (folded - self.max + 11) / 12 * 12;
+                }
+                // The register can be too narrow to hold this pitch class.
+                if !(self.min..=self.max).contains(&folded) {
+                    return None;
+                }
+                folded
+            }
+            RangePolicy::Clamp => note.clamp(self.min, self.max),
+            RangePolicy::Drop => return None,
+        };
+        u8::try_from(fitted).ok()
+    }
+
+    /**
+     * Applies the policy to every note of a chord, keeping their velocity.
+     */
+    pub fn fit_chord(&self, chord: &Chord) -> Chord {
+        chord.map_notes(self, |note| note)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full_range(policy: RangePolicy) -> NoteRange {
+        NoteRange {
+            policy,
+            min: 0,
+            max: 127,
+        }
+    }
+
+    #[test]
+    fn fold_moves_notes_by_octaves_into_the_midi_range() {
+        let range = full_range(RangePolicy::Fold);
+        assert_eq!(range.fit(0), Some(0));
+        assert_eq!(range.fit(127), Some(127));
+        assert_eq!(range.fit(-1), Some(11));
+        assert_eq!(range.fit(-12), Some(0));
+        assert_eq!(range.fit(-25), Some(11));
+        assert_eq!(range.fit(128), Some(116));
+        assert_eq!(range.fit(140), Some(116));
+    }
+
+    #[test]
+    fn fold_keeps_the_pitch_class_in_the_register() {
+        let range = NoteRange {
+            policy: RangePolicy::Fold,
+            min: 48,
+            max: 72,
+        };
+        assert_eq!(range.fit(30), Some(54));
+        assert_eq!(range.fit(85), Some(61));
+    }
+
+    #[test]
+    fn fold_drops_the_pitch_classes_missing_from_a_narrow_register() {
+        let range = NoteRange {
+            policy: RangePolicy::Fold,
+            min: 60,
+            max: 64,
+        };
+        assert_eq!(range.fit(74), Some(62));
+        assert_eq!(range.fit(66), None);
+    }
+
+    #[test]
+    fn clamp_replaces_notes_with_the_closest_bound() {
+        let range = full_range(RangePolicy::Clamp);
+        assert_eq!(range.fit(-3), Some(0));
+        assert_eq!(range.fit(200), Some(127));
+        assert_eq!(range.fit(64), Some(64));
+    }
+
+    #[test]
+    fn drop_removes_notes_out_of_the_register() {
+        let range = full_range(RangePolicy::Drop);
+        assert_eq!(range.fit(-1), None);
+        assert_eq!(range.fit(128), None);
+        assert_eq!(range.fit(0), Some(0));
+        assert_eq!(range.fit(127), Some(127));
+    }
+}
@@ -1,19 +1,91 @@
-use std::sync::{atomic::AtomicBool, Arc};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, RwLock,
+};
 
 use nih_plug::prelude::*;
+use nih_plug_egui::EguiState;
 
-const NOTE_NAMES: [&str; 12] = [
+use crate::{
+    arp_processor::NOTE_DIVISIONS,
+    bass_processor::BassNote,
+    diatonic::{ChordSize, OutOfScale},
+    editor,
+    gesture::GestureAction,
+    key::Mode,
+    note_range::RangePolicy,
+    strum::StrumDirection,
+    substitution::RuleCondition,
+    transform::Transform,
+    velocity::VelocityMode,
+    voicing::{Inversion, Variation, Voicing},
+    zone::{Zone, ZoneMode},
+};
+
+pub(crate) const NOTE_NAMES: [&str; 12] = [
     "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
 ];
-const NB_INTERVALS: usize = 6;
+pub(crate) const NB_INTERVALS: usize = 12;
+/**
+ * The number of intervals before the slots had an enabled flag.
+ * These slots are enabled by default so that the projects saved with them sound the same.
+ */
+const NB_LEGACY_INTERVALS: usize = 6;
+/**
+ * The number of velocity layers on top of the main intervals of a note.
+ */
+pub(crate) const NB_VELOCITY_LAYERS: usize = 2;
+/**
+ * The number of note maps that can be switched between, the first one is the main map.
+ */
+pub const NB_BANKS: usize = 8;
 
 /**
  * Represents one interval slider for a note.
  */
 #[derive(Params)]
 pub struct IntervalParam {
+    #[id = "interval_on"]
+    pub enabled: BoolParam,
     #[id = "interval"]
     pub interval: IntParam,
+    #[id = "velocity"]
+    pub velocity: FloatParam,
+    /**
+     * 0 follows the output channel, otherwise the note is sent to this channel.
+     */
+    #[id = "channel"]
+    pub channel: IntParam,
+    /**
+     * The chance for the interval to be played on each trigger.
+     */
+    #[id = "probability"]
+    pub probability: FloatParam,
+}
+
+/**
+ * One interval slot of a velocity layer.
+ * The velocity, channel and probability of the slot are shared with the main intervals.
+ */
+#[derive(Params)]
+pub struct LayerIntervalParam {
+    #[id = "layer_interval_on"]
+    pub enabled: BoolParam,
+    #[id = "layer_interval"]
+    pub interval: IntParam,
+}
+
+/**
+ * An alternative set of intervals, played instead of the main ones above a velocity threshold.
+ */
+#[derive(Params)]
+pub struct VelocityLayerParam {
+    #[id = "layer_on"]
+    pub activated: BoolParam,
+    #[id = "layer_min_velocity"]
+    pub min_velocity: FloatParam,
+    #[nested(array, group = "Layer Intervals")]
+    pub intervals: [LayerIntervalParam; NB_INTERVALS],
 }
 
 /**
@@ -26,8 +98,24 @@ pub struct NoteParam {
     pub active: BoolParam,
     #[id = "transpose"]
     pub transpose: IntParam,
+    /**
+     * Added to the global octave transpose for this note only.
+     */
+    #[id = "octave"]
+    pub octave: IntParam,
+    /**
+     * Also play the base note at its original octave when the note is octave transposed.
+     */
+    #[id = "keep_root"]
+    pub keep_root: BoolParam,
+    #[id = "root_velocity"]
+    pub root_velocity: FloatParam,
+    #[id = "root_channel"]
+    pub root_channel: IntParam,
     #[nested(array, group = "Intervals")]
     pub intervals: [IntervalParam; NB_INTERVALS],
+    #[nested(array, group = "Velocity Layers")]
+    pub layers: [VelocityLayerParam; NB_VELOCITY_LAYERS],
 }
 
 #[derive(Params)]
@@ -40,27 +128,363 @@ pub struct ArpParams {
     pub speed: FloatParam,
     #[id = "arp_rate"]
     pub rate: IntParam,
+    /**
+     * Plays the notes of the chord from the highest to the lowest.
+     */
+    #[id = "arp_retrograde"]
+    pub retrograde: BoolParam,
+}
+
+#[derive(Params)]
+pub struct BassParams {
+    #[id = "bass_on"]
+    pub activated: BoolParam,
+    /**
+     * The tone of the recognized chord that is sent to the output.
+     */
+    #[id = "bass_note"]
+    pub note: EnumParam<BassNote>,
+    #[id = "bass_octave"]
+    pub octave: IntParam,
+}
+
+/**
+ * An alternative note map, used instead of the main one when its bank is selected.
+ */
+#[derive(Params)]
+pub struct BankParams {
+    #[nested(array, group = "Notes")]
+    pub notes: [NoteParam; 12],
+}
+
+/**
+ * Filters the bounces and ghost notes of worn keyboards and pedalboards.
+ */
+#[derive(Params)]
+pub struct DebounceParams {
+    /**
+     * The minimum time between two note ons of a key, in ms.
+     */
+    #[id = "debounce_retrigger"]
+    pub retrigger_time: FloatParam,
+    /**
+     * The note ons softer than this are ignored.
+     */
+    #[id = "debounce_velocity"]
+    pub velocity_threshold: FloatParam,
+    /**
+     * The note ons that come this soon after the note off of their key are ignored, in ms. 0 to disable.
+     */
+    #[id = "debounce_release"]
+    pub release_guard: FloatParam,
+}
+
+/**
+ * The actions run by the double taps and long presses of the keys that play chords.
+ */
+#[derive(Params)]
+pub struct GestureParams {
+    /**
+     * The maximum time between the two note ons of a double tap, in ms.
+     */
+    #[id = "double_tap_window"]
+    pub double_tap_window: FloatParam,
+    #[id = "double_tap_action"]
+    pub double_tap_action: EnumParam<GestureAction>,
+    /**
+     * The time a key must be held to make a long press, in ms.
+     */
+    #[id = "long_press_time"]
+    pub long_press_time: FloatParam,
+    #[id = "long_press_action"]
+    pub long_press_action: EnumParam<GestureAction>,
+}
+
+/**
+ * Fills the note mapping of the active bank with the chords of a key.
+ */
+#[derive(Params)]
+pub struct DiatonicParams {
+    #[id = "diatonic_key"]
+    pub key: IntParam,
+    #[id = "diatonic_mode"]
+    pub mode: EnumParam<Mode>,
+    #[id = "diatonic_size"]
+    pub size: EnumParam<ChordSize>,
+    #[id = "diatonic_out_of_scale"]
+    pub out_of_scale: EnumParam<OutOfScale>,
+    /**
+     * Arms the fill. While it's on, the keys of the main note map play the diatonic chords.
+     * The editor writes them into the note panels of the playing bank, then the param goes back off.
+     */
+    #[id = "diatonic_fill"]
+    pub fill: BoolParam,
+}
+
+/**
+ * When a chord substitution rule is applied.
+ */
+#[derive(Params)]
+pub struct RuleParams {
+    #[id = "condition"]
+    pub condition: EnumParam<RuleCondition>,
+    #[id = "probability"]
+    pub probability: FloatParam,
+    /**
+     * The CC that applies the rule while it's held.
+     */
+    #[id = "cc"]
+    pub cc: IntParam,
+}
+
+/**
+ * Steps through the chords of a progression with a few keys, instead of the note mapping.
+ */
+#[derive(Params)]
+pub struct ProgressionParams {
+    #[id = "progression_on"]
+    pub activated: BoolParam,
+    /**
+     * The key that plays the next chord, -1 for any key.
+     */
+    #[id = "progression_next"]
+    pub next_note: IntParam,
+    /**
+     * The key that plays the previous chord, -1 for none.
+     */
+    #[id = "progression_previous"]
+    pub previous_note: IntParam,
+    /**
+     * The key that goes back to the first chord, -1 for none.
+     */
+    #[id = "progression_reset"]
+    pub reset_note: IntParam,
+    #[id = "progression_octave"]
+    pub octave: IntParam,
+}
+
+#[derive(Params)]
+pub struct ChordParams {
+    /**
+     * The time between two consecutive notes of the chord, in ms.
+     */
+    #[id = "strum_time"]
+    pub time: FloatParam,
+    #[id = "strum_sync"]
+    pub synced: BoolParam,
+    /**
+     * The index of the note division used when the strum is synced.
+     */
+    #[id = "strum_rate"]
+    pub rate: IntParam,
+    #[id = "strum_direction"]
+    pub direction: EnumParam<StrumDirection>,
+    #[id = "humanize_timing"]
+    pub humanize_timing: FloatParam,
+    #[id = "humanize_velocity"]
+    pub humanize_velocity: FloatParam,
+    /**
+     * Only the tones that change between two chords are retriggered, the common ones keep sounding.
+     */
+    #[id = "legato"]
+    pub legato: BoolParam,
+    /**
+     * The chords last a fixed time after their note on, whenever their key is released.
+     */
+    #[id = "gate_on"]
+    pub gate: BoolParam,
+    /**
+     * The length of the chords in gate mode, in ms.
+     */
+    #[id = "gate_time"]
+    pub gate_time: FloatParam,
+    #[id = "gate_sync"]
+    pub gate_synced: BoolParam,
+    /**
+     * The index of the note division used when the gate is synced.
+     */
+    #[id = "gate_rate"]
+    pub gate_rate: IntParam,
+}
+
+#[derive(Params)]
+pub struct SplitParams {
+    #[id = "split_on"]
+    pub activated: BoolParam,
+    /**
+     * The first key of the upper zone.
+     */
+    #[id = "split_note"]
+    pub note: IntParam,
+    #[id = "lower_mode"]
+    pub lower_mode: EnumParam<ZoneMode>,
+}
+
+/**
+ * The upper zone of a split keyboard, with its own mapping.
+ * The lower zone uses the main params.
+ */
+#[derive(Params)]
+pub struct ZoneParams {
+    #[id = "mode"]
+    pub mode: EnumParam<ZoneMode>,
+    #[id = "out_channel"]
+    pub out_channel: IntParam,
+    #[id = "octave_transpose"]
+    pub octave_transpose: IntParam,
+    #[nested(array, group = "Notes")]
+    pub notes: [NoteParam; 12],
 }
 
 #[derive(Params)]
 pub struct MidiTransposerParams {
+    #[persist = "editor-state"]
+    pub editor_state: Arc<EguiState>,
+    /**
+     * The chord symbols of the progression, separated by spaces.
+     */
+    #[persist = "progression"]
+    pub progression_chords: Arc<RwLock<String>>,
+    /**
+     * The chord symbols given to single keys, like "C2=Am7 C#2=D9".
+     */
+    #[persist = "key-map"]
+    pub key_map_chords: Arc<RwLock<String>>,
+    /**
+     * The bank of note maps in use. It follows the bank param, and it's also changed by the key-switches
+     * and the program changes, which can't be written to the param from the audio thread.
+     */
+    pub active_bank: Arc<AtomicUsize>,
+    #[id = "bank"]
+    pub bank: IntParam,
+    /**
+     * The key that selects the first bank, the next keys select the next banks. -1 for none.
+     */
+    #[id = "bank_keyswitch"]
+    pub bank_keyswitch: IntParam,
     #[id = "in_channel"]
     pub in_channel: IntParam,
     #[id = "out_channel"]
     pub out_channel: IntParam,
     #[id = "octave_transpose"]
     pub octave_transpose: IntParam,
+    /**
+     * When armed, the next chord played is written into the note panel of its lowest note.
+     * Only the editor can write the chord, so the param is hidden from the host.
+     */
+    #[id = "learn"]
+    pub learn: BoolParam,
+    /**
+     * Keeps the chord sounding after its key is released, until the next note,
+     * the same key again or a panic.
+     */
+    #[id = "hold"]
+    pub hold: BoolParam,
+    /**
+     * The keys of the absolute key map play their own chord instead of the chord of their pitch class.
+     */
+    #[id = "key_map"]
+    pub key_map: BoolParam,
+    #[id = "range_policy"]
+    pub range_policy: EnumParam<RangePolicy>,
+    #[id = "range_min"]
+    pub range_min: IntParam,
+    #[id = "range_max"]
+    pub range_max: IntParam,
+    #[id = "inversion"]
+    pub inversion: EnumParam<Inversion>,
+    #[id = "voicing"]
+    pub voicing: EnumParam<Voicing>,
+    #[id = "variation"]
+    pub variation: EnumParam<Variation>,
+    /**
+     * The seed of the random variations and intervals, the renders are the same for a given seed.
+     */
+    #[id = "seed"]
+    pub seed: IntParam,
+    #[id = "transform"]
+    pub transform: EnumParam<Transform>,
+    /**
+     * The key center of the mapping as a pitch class, used by the negative harmony and the mode shift.
+     */
+    #[id = "key_center"]
+    pub key_center: IntParam,
+    /**
+     * Transposes the whole mapping, in semitones.
+     */
+    #[id = "key_shift"]
+    pub key_shift: IntParam,
+    /**
+     * The mode the mapping is re-spelled in, the mapping itself is written in major.
+     */
+    #[id = "mode_shift"]
+    pub mode_shift: EnumParam<Mode>,
+    /**
+     * The note the chromatic inversion mirrors the chords around.
+     */
+    #[id = "pivot"]
+    pub pivot: IntParam,
+    #[id = "velocity_mode"]
+    pub velocity_mode: EnumParam<VelocityMode>,
+    #[id = "fixed_velocity"]
+    pub fixed_velocity: FloatParam,
+    #[id = "velocity_min"]
+    pub velocity_min: FloatParam,
+    #[id = "velocity_max"]
+    pub velocity_max: FloatParam,
     #[nested(group = "Arpeggiator")]
     pub arp: Arc<ArpParams>,
+    #[nested(group = "Chord")]
+    pub chord: Arc<ChordParams>,
+    #[nested(group = "Chord to Bass")]
+    pub bass: Arc<BassParams>,
+    #[nested(group = "Progression")]
+    pub progression: ProgressionParams,
+    #[nested(group = "Input Filter")]
+    pub debounce: DebounceParams,
+    #[nested(group = "Gestures")]
+    pub gestures: GestureParams,
+    #[nested(group = "Diatonic Fill")]
+    pub diatonic: DiatonicParams,
+    #[nested(id_prefix = "tritone", group = "Tritone Substitution")]
+    pub tritone_sub: RuleParams,
+    #[nested(id_prefix = "relative", group = "Relative Swap")]
+    pub relative_sub: RuleParams,
+    #[nested(id_prefix = "diminished", group = "Diminished Passing Chord")]
+    pub diminished_sub: RuleParams,
+    #[nested(group = "Split")]
+    pub split: SplitParams,
+    #[nested(id_prefix = "upper", group = "Upper Zone")]
+    pub upper_zone: ZoneParams,
     #[nested(array, group = "Notes")]
     pub notes: [NoteParam; 12],
+    #[nested(array, group = "Banks")]
+    pub banks: [BankParams; NB_BANKS - 1],
 }
 
 impl MidiTransposerParams {
-    pub fn new(should_reset_arp: Arc<AtomicBool>) -> Self {
-        let all_notes: [usize; 12] = core::array::from_fn(|i| i + 1);
-        let all_intervals: [usize; NB_INTERVALS] = core::array::from_fn(|i| i + 1);
+    pub fn new(should_update_processor: Arc<AtomicBool>) -> Self {
+        let should_update_arp = should_update_processor.clone();
+        let active_bank = Arc::new(AtomicUsize::new(0));
+        let bank_active_bank = active_bank.clone();
+        let all_banks: [usize; NB_BANKS - 1] = core::array::from_fn(|i| i + 2);
         Self {
+            editor_state: editor::default_state(),
+            progression_chords: Arc::new(RwLock::new(String::new())),
+            key_map_chords: Arc::new(RwLock::new(String::new())),
+            active_bank,
+            bank: IntParam::new(
+                "Bank",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: NB_BANKS as i32,
+                },
+            )
+            .with_callback(Arc::new(move |value| {
+                bank_active_bank.store(value as usize - 1, Ordering::Release);
+            })),
+            bank_keyswitch: key_param("Bank Key-switch", -1, "None"),
             in_channel: IntParam::new("Input Channel", 1, IntRange::Linear { min: 0, max: 16 }),
             out_channel: IntParam::new("Output Channel", 1, IntRange::Linear { min: 0, max: 16 }),
             octave_transpose: IntParam::new(
@@ -68,29 +492,484 @@ impl MidiTransposerParams {
                 0,
                 IntRange::Linear { min: -1, max: 4 },
             ),
+            learn: BoolParam::new("Chord Learn", false).hide(),
+            hold: BoolParam::new("Chord Hold", false),
+            key_map: BoolParam::new("Absolute Key Map", false),
+            range_policy: EnumParam::new("Out of Range Notes", RangePolicy::Drop),
+            range_min: IntParam::new("Lowest Note", 0, IntRange::Linear { min: 0, max: 127 })
+                .with_value_to_string(formatters::v2s_i32_note_formatter())
+                .with_string_to_value(formatters::s2v_i32_note_formatter()),
+            range_max: IntParam::new("Highest Note", 127, IntRange::Linear { min: 0, max: 127 })
+                .with_value_to_string(formatters::v2s_i32_note_formatter())
+                .with_string_to_value(formatters::s2v_i32_note_formatter()),
+            inversion: EnumParam::new("Inversion", Inversion::Root),
+            voicing: EnumParam::new("Voicing", Voicing::Close),
+            variation: EnumParam::new("Variation", Variation::Off),
+            seed: IntParam::new("Random Seed", 1, IntRange::Linear { min: 1, max: 9999 }),
+            transform: EnumParam::new("Transform", Transform::Off),
+            key_center: pitch_class_param("Key Center"),
+            key_shift: IntParam::new("Key Shift", 0, IntRange::Linear { min: -11, max: 11 }),
+            mode_shift: EnumParam::new("Mode Shift", Mode::Ionian),
+            pivot: IntParam::new("Pivot Note", 60, IntRange::Linear { min: 0, max: 127 })
+                .with_value_to_string(formatters::v2s_i32_note_formatter())
+                .with_string_to_value(formatters::s2v_i32_note_formatter()),
+            velocity_mode: EnumParam::new("Velocity Mode", VelocityMode::Follow),
+            fixed_velocity: FloatParam::new(
+                "Fixed Velocity",
+                0.8,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+            velocity_min: FloatParam::new(
+                "Velocity Min",
+                0.3,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+            velocity_max: FloatParam::new(
+                "Velocity Max",
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
             arp: Arc::new(ArpParams {
                 activated: BoolParam::new("Arp On/Off", false).with_callback(Arc::new(move |_| {
-                    should_reset_arp.store(true, std::sync::atomic::Ordering::Release);
+                    should_update_arp.store(true, std::sync::atomic::Ordering::Release);
                 })),
                 synced: BoolParam::new("Arp Sync", false),
                 speed: FloatParam::new("Arp Speed", 1.0, FloatRange::Linear { min: 0.1, max: 1.0 }),
                 rate: IntParam::new("Arp Rate", 0, IntRange::Linear { min: 0, max: 8 }),
+                retrograde: BoolParam::new("Arp Retrograde", false),
+            }),
+            chord: Arc::new(ChordParams {
+                time: FloatParam::new(
+                    "Strum Time",
+                    0.0,
+                    FloatRange::Skewed {
+                        min: 0.0,
+                        max: 200.0,
+                        factor: FloatRange::skew_factor(-1.0),
+                    },
+                )
+                .with_unit(" ms")
+                .with_step_size(0.1),
+                synced: BoolParam::new("Strum Sync", false),
+                rate: IntParam::new(
+                    "Strum Rate",
+                    NOTE_DIVISIONS.len() as i32 - 1,
+                    IntRange::Linear {
+                        min: 0,
+                        max: NOTE_DIVISIONS.len() as i32 - 1,
+                    },
+                )
+                .with_value_to_string(Arc::new(|value| {
+                    NOTE_DIVISIONS[value as usize].label.to_string()
+                })),
+                direction: EnumParam::new("Strum Direction", StrumDirection::Up),
+                humanize_timing: FloatParam::new(
+                    "Humanize Timing",
+                    0.0,
+                    FloatRange::Linear {
+                        min: 0.0,
+                        max: 50.0,
+                    },
+                )
+                .with_unit(" ms")
+                .with_step_size(0.1),
+                humanize_velocity: FloatParam::new(
+                    "Humanize Velocity",
+                    0.0,
+                    FloatRange::Linear { min: 0.0, max: 0.5 },
+                )
+                .with_unit(" %")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+                legato: BoolParam::new("Legato", false),
+                gate: BoolParam::new("Gate", false),
+                gate_time: FloatParam::new(
+                    "Gate Time",
+                    250.0,
+                    FloatRange::Skewed {
+                        min: 5.0,
+                        max: 4000.0,
+                        factor: FloatRange::skew_factor(-2.0),
+                    },
+                )
+                .with_unit(" ms")
+                .with_step_size(0.1),
+                gate_synced: BoolParam::new("Gate Sync", false),
+                gate_rate: IntParam::new(
+                    "Gate Rate",
+                    // 1/8
+                    6,
+                    IntRange::Linear {
+                        min: 0,
+                        max: NOTE_DIVISIONS.len() as i32 - 1,
+                    },
+                )
+                .with_value_to_string(Arc::new(|value| {
+                    NOTE_DIVISIONS[value as usize].label.to_string()
+                })),
+            }),
+            bass: Arc::new(BassParams {
+                activated: BoolParam::new("Chord to Bass On/Off", false).with_callback(Arc::new(
+                    move |_| {
+                        should_update_processor.store(true, std::sync::atomic::Ordering::Release);
+                    },
+                )),
+                note: EnumParam::new("Bass Note", BassNote::Root),
+                octave: IntParam::new("Bass Octave", 2, IntRange::Linear { min: 0, max: 6 }),
             }),
-            notes: all_notes.map(|note| NoteParam {
-                active: BoolParam::new(format!("Activate {}", NOTE_NAMES[note - 1]), true),
-                transpose: IntParam::new(
-                    format!("{} semitones transpose", NOTE_NAMES[note - 1]),
+            progression: ProgressionParams {
+                activated: BoolParam::new("Progression On/Off", false),
+                next_note: key_param("Next Chord Key", -1, "Any"),
+                previous_note: key_param("Previous Chord Key", -1, "None"),
+                reset_note: key_param("First Chord Key", -1, "None"),
+                octave: IntParam::new("Progression Octave", 3, IntRange::Linear { min: 0, max: 7 }),
+            },
+            debounce: DebounceParams {
+                retrigger_time: debounce_time_param("Minimum Retrigger Time"),
+                velocity_threshold: FloatParam::new(
+                    "Velocity Threshold",
+                    0.0,
+                    FloatRange::Linear { min: 0.0, max: 1.0 },
+                )
+                .with_unit(" %")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+                release_guard: debounce_time_param("Release Guard Time"),
+            },
+            gestures: GestureParams {
+                double_tap_window: FloatParam::new(
+                    "Double Tap Window",
+                    300.0,
+                    FloatRange::Linear {
+                        min: 50.0,
+                        max: 1000.0,
+                    },
+                )
+                .with_unit(" ms")
+                .with_step_size(1.0),
+                double_tap_action: EnumParam::new("Double Tap Action", GestureAction::None),
+                long_press_time: FloatParam::new(
+                    "Long Press Time",
+                    800.0,
+                    FloatRange::Linear {
+                        min: 200.0,
+                        max: 3000.0,
+                    },
+                )
+                .with_unit(" ms")
+                .with_step_size(1.0),
+                long_press_action: EnumParam::new("Long Press Action", GestureAction::None),
+            },
+            diatonic: DiatonicParams {
+                key: pitch_class_param("Diatonic Key"),
+                mode: EnumParam::new("Diatonic Scale", Mode::Ionian),
+                size: EnumParam::new("Diatonic Chord Size", ChordSize::Triad),
+                out_of_scale: EnumParam::new("Out of Scale Keys", OutOfScale::Mute),
+                fill: BoolParam::new("Fill Map", false),
+            },
+            tritone_sub: rule_params("Tritone Substitution"),
+            relative_sub: rule_params("Relative Swap"),
+            diminished_sub: rule_params("Diminished Passing Chord"),
+            split: SplitParams {
+                activated: BoolParam::new("Split On/Off", false),
+                note: IntParam::new("Split Note", 40, IntRange::Linear { min: 0, max: 127 })
+                    .with_value_to_string(formatters::v2s_i32_note_formatter())
+                    .with_string_to_value(formatters::s2v_i32_note_formatter()),
+                lower_mode: EnumParam::new("Lower Zone Mode", ZoneMode::Chords),
+            },
+            upper_zone: ZoneParams {
+                mode: EnumParam::new("Upper Zone Mode", ZoneMode::PassThrough),
+                out_channel: IntParam::new(
+                    "Upper Zone Output Channel",
+                    1,
+                    IntRange::Linear { min: 0, max: 16 },
+                ),
+                octave_transpose: IntParam::new(
+                    "Upper Zone Octave Transpose",
                     0,
-                    IntRange::Linear { min: -12, max: 12 },
+                    IntRange::Linear { min: -1, max: 4 },
                 ),
-                intervals: all_intervals.map(|interval| IntervalParam {
+                notes: note_params("Upper ", false),
+            },
+            notes: note_params("", false),
+            banks: all_banks.map(|bank| BankParams {
+                // The banks are edited in the editor, with the chord learn and the diatonic fill,
+                // there are too many of their params for the hosts to list them.
+                notes: note_params(&format!("Bank {bank} "), true),
+            }),
+        }
+    }
+
+    /**
+     * Returns the note map of the active bank.
+     */
+    pub fn bank_notes(&self) -> &[NoteParam; 12] {
+        match self.active_bank.load(Ordering::Acquire) {
+            0 => &self.notes,
+            bank => &self.banks[bank.min(NB_BANKS - 1) - 1].notes,
+        }
+    }
+
+    /**
+     * Returns the index of the note panel that maps a key, the key shift rotates the whole mapping.
+     */
+    pub fn note_index(&self, note: u8) -> usize {
+        ((note % 12) as i32 - self.key_shift.value()).rem_euclid(12) as usize
+    }
+
+    pub fn select_bank(&self, bank: usize) {
+        self.active_bank
+            .store(bank.min(NB_BANKS - 1), Ordering::Release);
+    }
+
+    /**
+     * Returns the bank selected by a key if it's a key-switch.
+     */
+    pub fn keyswitch_bank(&self, note: u8) -> Option<usize> {
+        let first_key = self.bank_keyswitch.value();
+        let bank = note as i32 - first_key;
+        (first_key >= 0 && (0..NB_BANKS as i32).contains(&bank)).then_some(bank as usize)
+    }
+
+    /**
+     * Returns true if a key is above the split.
+     */
+    pub fn in_upper_zone(&self, note: u8) -> bool {
+        self.split.activated.value() && note as i32 >= self.split.note.value()
+    }
+
+    /**
+     * Returns the zone a key belongs to. The lower zone uses the main params,
+     * and it covers the whole keyboard when the split is off.
+     */
+    pub fn zone(&self, note: u8) -> Zone<'_> {
+        if self.in_upper_zone(note) {
+            Zone {
+                mode: self.upper_zone.mode.value(),
+                notes: &self.upper_zone.notes,
+                octave_transpose: self.upper_zone.octave_transpose.value(),
+                out_channel: self.upper_zone.out_channel.value(),
+            }
+        } else {
+            Zone {
+                mode: if self.split.activated.value() {
+                    self.split.lower_mode.value()
+                } else {
+                    ZoneMode::Chords
+                },
+                notes: self.bank_notes(),
+                octave_transpose: self.octave_transpose.value(),
+                out_channel: self.out_channel.value(),
+            }
+        }
+    }
+}
+
+/**
+ * Builds the 12 note panels, the prefix allows to tell apart the params of each zone.
+ * The hidden panels are not shown or automated by the host, and the velocity layers are not automated.
+ */
+fn note_params(name_prefix: &str, hidden: bool) -> [NoteParam; 12] {
+    let all_notes: [usize; 12] = core::array::from_fn(|i| i + 1);
+    let all_intervals: [usize; NB_INTERVALS] = core::array::from_fn(|i| i + 1);
+    let all_layers: [usize; NB_VELOCITY_LAYERS] = core::array::from_fn(|i| i + 2);
+    all_notes.map(|note| {
+        let note_name = format!("{name_prefix}{}", NOTE_NAMES[note - 1]);
+        NoteParam {
+            active: BoolParam::new(format!("Activate {note_name}"), true).hide_if(hidden),
+            transpose: IntParam::new(
+                format!("{note_name} semitones transpose"),
+                0,
+                IntRange::Linear { min: -12, max: 12 },
+            )
+            .hide_if(hidden),
+            octave: IntParam::new(
+                format!("{note_name} octave offset"),
+                0,
+                IntRange::Linear { min: -2, max: 2 },
+            )
+            .hide_if(hidden),
+            keep_root: BoolParam::new(format!("{note_name} keep original octave"), true)
+                .hide_if(hidden),
+            root_velocity: velocity_scale_param(format!("{note_name} root velocity"))
+                .hide_if(hidden),
+            root_channel: channel_routing_param(format!("{note_name} root channel"))
+                .hide_if(hidden),
+            intervals: all_intervals.map(|interval| IntervalParam {
+                enabled: BoolParam::new(
+                    format!("{note_name} interval {interval} on"),
+                    interval <= NB_LEGACY_INTERVALS,
+                )
+                .hide_if(hidden),
+                interval: IntParam::new(
+                    format!("{note_name} interval {interval}"),
+                    0,
+                    IntRange::Linear { min: -24, max: 24 },
+                )
+                .hide_if(hidden),
+                velocity: velocity_scale_param(format!("{note_name} interval {interval} velocity"))
+                    .hide_if(hidden),
+                channel: channel_routing_param(format!("{note_name} interval {interval} channel"))
+                    .hide_if(hidden),
+                probability: FloatParam::new(
+                    format!("{note_name} interval {interval} probability"),
+                    1.0,
+                    FloatRange::Linear { min: 0.0, max: 1.0 },
+                )
+                .with_unit(" %")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .hide_if(hidden),
+            }),
+            layers: all_layers.map(|layer| VelocityLayerParam {
+                activated: BoolParam::new(format!("{note_name} velocity layer {layer} on"), false)
+                    .non_automatable()
+                    .hide_if(hidden),
+                min_velocity: FloatParam::new(
+                    format!("{note_name} velocity layer {layer} threshold"),
+                    // The first layer is 2, the main intervals are the first layer.
+                    if layer == 2 { 0.5 } else { 0.8 },
+                    FloatRange::Linear { min: 0.0, max: 1.0 },
+                )
+                .with_unit(" %")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .non_automatable()
+                .hide_if(hidden),
+                intervals: all_intervals.map(|interval| LayerIntervalParam {
+                    enabled: BoolParam::new(
+                        format!("{note_name} velocity layer {layer} interval {interval} on"),
+                        false,
+                    )
+                    .non_automatable()
+                    .hide_if(hidden),
                     interval: IntParam::new(
-                        format!("{} interval {interval}", NOTE_NAMES[note - 1]),
+                        format!("{note_name} velocity layer {layer} interval {interval}"),
                         0,
-                        IntRange::Linear { min: -12, max: 12 },
-                    ),
+                        IntRange::Linear { min: -24, max: 24 },
+                    )
+                    .non_automatable()
+                    .hide_if(hidden),
                 }),
             }),
         }
+    })
+}
+
+/**
+ * A velocity scale applied to one note of the chord, from 0% to 200%.
+ */
+fn velocity_scale_param(name: String) -> FloatParam {
+    FloatParam::new(name, 1.0, FloatRange::Linear { min: 0.0, max: 2.0 })
+        .with_unit(" %")
+        .with_value_to_string(formatters::v2s_f32_percentage(0))
+        .with_string_to_value(formatters::s2v_f32_percentage())
+}
+
+/**
+ * A key of the keyboard, where -1 has a special meaning given by its label.
+ */
+fn key_param(name: &str, default: i32, none_label: &'static str) -> IntParam {
+    let note_formatter = formatters::v2s_i32_note_formatter();
+    let note_parser = formatters::s2v_i32_note_formatter();
+    IntParam::new(name, default, IntRange::Linear { min: -1, max: 127 })
+        .with_value_to_string(Arc::new(move |value| match value {
+            -1 => String::from(none_label),
+            _ => note_formatter(value),
+        }))
+        .with_string_to_value(Arc::new(move |string| {
+            if string.trim().eq_ignore_ascii_case(none_label) {
+                Some(-1)
+            } else {
+                note_parser(string)
+            }
+        }))
+}
+
+/**
+ * A pitch class, shown as a note name without octave.
+ */
+fn pitch_class_param(name: &str) -> IntParam {
+    IntParam::new(name, 0, IntRange::Linear { min: 0, max: 11 })
+        .with_value_to_string(Arc::new(|value| NOTE_NAMES[value as usize].to_string()))
+        .with_string_to_value(Arc::new(|string| {
+            NOTE_NAMES
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(string.trim()))
+                .map(|index| index as i32)
+        }))
+}
+
+/**
+ * Hides a param from the host when it belongs to a hidden group.
+ */
+trait HideIf: Sized {
+    fn hide_if(self, hidden: bool) -> Self;
+}
+
+macro_rules! impl_hide_if {
+    ($($param:ty),*) => {
+        $(impl HideIf for $param {
+            fn hide_if(self, hidden: bool) -> Self {
+                if hidden {
+                    self.hide()
+                } else {
+                    self
+                }
+            }
+        })*
+    };
+}
+
+impl_hide_if!(BoolParam, IntParam, FloatParam);
+
+fn rule_params(name: &str) -> RuleParams {
+    RuleParams {
+        condition: EnumParam::new(format!("{name} Condition"), RuleCondition::Off),
+        probability: FloatParam::new(
+            format!("{name} Probability"),
+            0.5,
+            FloatRange::Linear { min: 0.0, max: 1.0 },
+        )
+        .with_unit(" %")
+        .with_value_to_string(formatters::v2s_f32_percentage(0))
+        .with_string_to_value(formatters::s2v_f32_percentage()),
+        cc: IntParam::new(
+            format!("{name} CC"),
+            1,
+            IntRange::Linear { min: 0, max: 127 },
+        ),
     }
 }
+
+fn debounce_time_param(name: &str) -> FloatParam {
+    FloatParam::new(
+        name,
+        0.0,
+        FloatRange::Skewed {
+            min: 0.0,
+            max: 200.0,
+            factor: FloatRange::skew_factor(-1.0),
+        },
+    )
+    .with_unit(" ms")
+    .with_step_size(0.1)
+}
+
+/**
+ * The output channel of one note of the chord, 0 follows the global output channel.
+ */
+fn channel_routing_param(name: String) -> IntParam {
+    IntParam::new(name, 0, IntRange::Linear { min: 0, max: 16 })
+        .with_value_to_string(Arc::new(|value| match value {
+            0 => String::from("Follow"),
+            _ => value.to_string(),
+        }))
+        .with_string_to_value(Arc::new(|string| {
+            if string.trim().eq_ignore_ascii_case("follow") {
+                Some(0)
+            } else {
+                string.trim().parse().ok()
+            }
+        }))
+}
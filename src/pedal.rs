@@ -0,0 +1,188 @@
+use crate::Chord;
+
+pub(crate) const SUSTAIN_PEDAL: u8 = 64;
+pub(crate) const SOSTENUTO_PEDAL: u8 = 66;
+
+/**
+ * The note offs held back by the sustain and sostenuto pedals, per output channel.
+ */
+#[derive(Default)]
+pub(crate) struct Pedals {
+    sustain: bool,
+    sostenuto: bool,
+    /**
+     * The notes caught when the sostenuto pedal was pressed.
+     */
+    sostenuto_notes: [u128; 16],
+    /**
+     * The notes that should have been released but are kept sounding by a pedal.
+     */
+    sustained_notes: [u128; 16],
+}
+
+impl Pedals {
+    /**
+     * Updates the state of the pedals, and releases the notes they don't hold anymore.
+     * The sostenuto pedal catches the notes of the chord held when it's pressed.
+     */
+    pub fn update(
+        &mut self,
+        sustain: bool,
+        sostenuto: bool,
+        chord_held: Option<&Chord>,
+        release: impl FnMut(u8, u8),
+    ) {
+        if sostenuto != self.sostenuto {
+            self.sostenuto_notes = [0; 16];
+            if let (true, Some(chord)) = (sostenuto, chord_held) {
+                for note in (0..128).filter(|note| chord.contains(*note)) {
+                    for (channel, _) in chord.voices(note) {
+                        self.sostenuto_notes[channel as usize] |= 1 << note;
+                    }
+                }
+            }
+        }
+        self.sustain = sustain;
+        self.sostenuto = sostenuto;
+
+        if !self.sustain {
+            let sostenuto_notes = self.sostenuto_notes;
+            self.release_notes(release, |channel| !sostenuto_notes[channel]);
+        }
+    }
+
+    /**
+     * Returns true if the note off must be held back by a pedal, and keeps it for later.
+     */
+    pub fn defer_note_off(&mut self, note: u8, channel: u8) -> bool {
+        let channel = channel as usize & 15;
+        if self.sustain || self.sostenuto_notes[channel] & (1 << note) != 0 {
+            self.sustained_notes[channel] |= 1 << note;
+            true
+        } else {
+            false
+        }
+    }
+
+    /**
+     * Returns true if the note is still sounding because of a pedal, in which case it must be released
+     * before being played again. It's not held by the pedal anymore.
+     */
+    pub fn take_sustained_note(&mut self, note: u8, channel: u8) -> bool {
+        let channel = channel as usize & 15;
+        let sustained = self.sustained_notes[channel] & (1 << note) != 0;
+        self.sustained_notes[channel] &= !(1 << note);
+        sustained
+    }
+
+    /**
+     * Releases every note held by the pedals, whatever their state.
+     */
+    pub fn release_all(&mut self, release: impl FnMut(u8, u8)) {
+        self.release_notes(release, |_| u128::MAX);
+    }
+
+    fn release_notes(&mut self, mut release: impl FnMut(u8, u8), mask: impl Fn(usize) -> u128) {
+        for channel in 0..16 {
+            let notes = self.sustained_notes[channel] & mask(channel);
+            for note in (0..128).filter(|note| notes & (1 << note) != 0) {
+                release(note, channel as u8);
+            }
+            self.sustained_notes[channel] &= !notes;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn released(
+        pedals: &mut Pedals,
+        sustain: bool,
+        sostenuto: bool,
+        chord: Option<&Chord>,
+    ) -> Vec<(u8, u8)> {
+        let mut released = Vec::new();
+        pedals.update(sustain, sostenuto, chord, |note, channel| {
+            released.push((note, channel))
+        });
+        released
+    }
+
+    fn chord(notes: &[(u8, u8)]) -> Chord {
+        let mut chord = Chord::default();
+        for (note, channel) in notes {
+            chord.add_note(*note, 1.0, *channel);
+        }
+        chord
+    }
+
+    #[test]
+    fn the_sostenuto_catches_the_chord_held_when_it_is_pressed() {
+        let mut pedals = Pedals::default();
+        let held = chord(&[(60, 0), (64, 1)]);
+        released(&mut pedals, false, true, Some(&held));
+
+        assert!(pedals.defer_note_off(60, 0));
+        assert!(pedals.defer_note_off(64, 1));
+        // The same note on another channel, and the notes played after, are not caught.
+        assert!(!pedals.defer_note_off(64, 0));
+        assert!(!pedals.defer_note_off(67, 0));
+
+        assert_eq!(
+            released(&mut pedals, false, false, None),
+            vec![(60, 0), (64, 1)]
+        );
+    }
+
+    #[test]
+    fn lifting_the_sustain_keeps_the_notes_caught_by_the_sostenuto() {
+        let mut pedals = Pedals::default();
+        let held = chord(&[(60, 0)]);
+        released(&mut pedals, false, true, Some(&held));
+        released(&mut pedals, true, true, Some(&held));
+        assert!(pedals.defer_note_off(60, 0));
+        assert!(pedals.defer_note_off(67, 0));
+
+        assert_eq!(released(&mut pedals, false, true, None), vec![(67, 0)]);
+        assert_eq!(released(&mut pedals, false, false, None), vec![(60, 0)]);
+    }
+
+    #[test]
+    fn the_sostenuto_notes_stay_sustained_while_the_sustain_is_down() {
+        let mut pedals = Pedals::default();
+        let held = chord(&[(60, 0)]);
+        released(&mut pedals, true, true, Some(&held));
+        assert!(pedals.defer_note_off(60, 0));
+
+        assert_eq!(released(&mut pedals, true, false, None), vec![]);
+        assert_eq!(released(&mut pedals, false, false, None), vec![(60, 0)]);
+    }
+
+    #[test]
+    fn a_retriggered_note_is_taken_from_the_pedal() {
+        let mut pedals = Pedals::default();
+        released(&mut pedals, true, false, None);
+        assert!(pedals.defer_note_off(60, 0));
+
+        assert!(!pedals.take_sustained_note(60, 1));
+        assert!(pedals.take_sustained_note(60, 0));
+        assert!(!pedals.take_sustained_note(60, 0));
+        // The pedal doesn't release the note again when it's lifted.
+        assert_eq!(released(&mut pedals, false, false, None), vec![]);
+    }
+
+    #[test]
+    fn release_all_releases_the_notes_whatever_the_pedals() {
+        let mut pedals = Pedals::default();
+        released(&mut pedals, true, false, None);
+        pedals.defer_note_off(60, 0);
+        pedals.defer_note_off(62, 3);
+
+        let mut all_released = Vec::new();
+        pedals.release_all(|note, channel| all_released.push((note, channel)));
+        assert_eq!(all_released, vec![(60, 0), (62, 3)]);
+        assert_eq!(released(&mut pedals, false, false, None), vec![]);
+    }
+}
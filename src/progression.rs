@@ -0,0 +1,125 @@
+use crate::{
+    chord_symbol::{self, ChordSymbol},
+    params::ProgressionParams,
+};
+
+/**
+ * How a key moves through the progression.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Step {
+    Next,
+    Previous,
+    Reset,
+}
+
+/**
+ * Returns how a key moves through the progression, or None if it's mapped as usual.
+ * The next key can be any key that is not the previous or reset key.
+ */
+pub(crate) fn key_step(params: &ProgressionParams, note: u8) -> Option<Step> {
+    let note = note as i32;
+    if note == params.reset_note.value() {
+        Some(Step::Reset)
+    } else if note == params.previous_note.value() {
+        Some(Step::Previous)
+    } else if params.next_note.value() == -1 || note == params.next_note.value() {
+        Some(Step::Next)
+    } else {
+        None
+    }
+}
+
+/**
+ * The position in the progression, which is a list of chord symbols separated by spaces.
+ * The words that are not chord symbols, like dashes or bar lines, are skipped.
+ */
+#[derive(Default)]
+pub(crate) struct ProgressionStepper {
+    /**
+     * The chord playing, None before the first one.
+     */
+    position: Option<usize>,
+}
+
+impl ProgressionStepper {
+    /**
+     * Moves through the progression and returns the chord to play.
+     */
+    pub fn step(&mut self, progression: &str, step: Step) -> Option<ChordSymbol> {
+        let nb_chords = chords(progression).count();
+        if nb_chords == 0 {
+            return None;
+        }
+        let position = match (step, self.position) {
+            (Step::Reset, _) | (Step::Next, None) => 0,
+            (Step::Next, Some(position)) => (position + 1) % nb_chords,
+            (Step::Previous, None) => nb_chords - 1,
+            (Step::Previous, Some(position)) => (position + nb_chords - 1) % nb_chords,
+        };
+        self.position = Some(position);
+        chords(progression).nth(position)
+    }
+}
+
+fn chords(progression: &str) -> impl Iterator<Item = ChordSymbol> + '_ {
+    progression
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '|')
+        .filter_map(chord_symbol::parse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root(symbol: Option<ChordSymbol>) -> Option<u8> {
+        symbol.map(|symbol| symbol.root)
+    }
+
+    #[test]
+    fn steps_forward_and_wraps_around() {
+        let mut stepper = ProgressionStepper::default();
+        let progression = "Am7 D9 Gmaj7";
+        assert_eq!(root(stepper.step(progression, Step::Next)), Some(9));
+        assert_eq!(root(stepper.step(progression, Step::Next)), Some(2));
+        assert_eq!(root(stepper.step(progression, Step::Next)), Some(7));
+        assert_eq!(root(stepper.step(progression, Step::Next)), Some(9));
+    }
+
+    #[test]
+    fn steps_backward_and_wraps_around() {
+        let mut stepper = ProgressionStepper::default();
+        let progression = "Am7 D9 Gmaj7";
+        assert_eq!(root(stepper.step(progression, Step::Previous)), Some(7));
+        assert_eq!(root(stepper.step(progression, Step::Previous)), Some(2));
+        assert_eq!(root(stepper.step(progression, Step::Previous)), Some(9));
+        assert_eq!(root(stepper.step(progression, Step::Previous)), Some(7));
+    }
+
+    #[test]
+    fn reset_goes_back_to_the_first_chord() {
+        let mut stepper = ProgressionStepper::default();
+        let progression = "Am7 D9 Gmaj7";
+        stepper.step(progression, Step::Next);
+        stepper.step(progression, Step::Next);
+        assert_eq!(root(stepper.step(progression, Step::Reset)), Some(9));
+        assert_eq!(root(stepper.step(progression, Step::Next)), Some(2));
+    }
+
+    #[test]
+    fn skips_the_words_that_are_not_chords() {
+        let mut stepper = ProgressionStepper::default();
+        let progression = "| C - | F,G |";
+        assert_eq!(root(stepper.step(progression, Step::Next)), Some(0));
+        assert_eq!(root(stepper.step(progression, Step::Next)), Some(5));
+        assert_eq!(root(stepper.step(progression, Step::Next)), Some(7));
+        assert_eq!(root(stepper.step(progression, Step::Next)), Some(0));
+    }
+
+    #[test]
+    fn an_empty_progression_has_no_chord() {
+        let mut stepper = ProgressionStepper::default();
+        assert_eq!(stepper.step("", Step::Next), None);
+        assert_eq!(stepper.step("- |", Step::Previous), None);
+    }
+}
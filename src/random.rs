@@ -0,0 +1,74 @@
+/**
+ * A small xorshift pseudo random generator.
+ * It doesn't allocate, so it can be used on the audio thread, and it can be seeded.
+ */
+pub(crate) struct Random {
+    state: u64,
+}
+
+impl Random {
+    /**
+     * The seed is scrambled with splitmix64, since the xorshift state of a small seed
+     * would give a first draw of 0 and close seeds would give close draws.
+     */
+    pub fn new(seed: u64) -> Self {
+        let mut state = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        state ^= state >> 31;
+        Self {
+            // The xorshift state must never be 0.
+            state: state.max(1),
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /**
+     * Returns a value between 0 (included) and 1 (excluded).
+     */
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /**
+     * Returns a value between -1 (included) and 1 (excluded).
+     */
+    pub fn next_bipolar(&mut self) -> f32 {
+        self.next_f32() * 2.0 - 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_draw_of_small_seeds_is_not_zero() {
+        for seed in [1, 2, 42, 9999] {
+            assert!(Random::new(seed).next_f32() > 0.0, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn close_seeds_give_different_draws() {
+        let first_draws: Vec<u64> = (1..=100).map(|seed| Random::new(seed).next_u64()).collect();
+        for (index, draw) in first_draws.iter().enumerate() {
+            assert!(!first_draws[index + 1..].contains(draw));
+        }
+    }
+
+    #[test]
+    fn a_seed_always_gives_the_same_draws() {
+        let mut first = Random::new(42);
+        let mut second = Random::new(42);
+        for _ in 0..100 {
+            assert_eq!(first.next_u64(), second.next_u64());
+        }
+    }
+}
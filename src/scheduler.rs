@@ -0,0 +1,395 @@
+use nih_plug::{
+    context::process::ProcessContext,
+    midi::{NoteEvent, PluginNoteEvent},
+};
+
+use crate::MidiTransposer;
+
+/**
+ * The note ons only take part of the queue, the rest is kept for the note offs.
+ * Since there are 128 notes on 16 channels, a full queue always holds two note offs for the same note.
+ */
+const NOTE_ON_CAPACITY: usize = 1024;
+const CAPACITY: usize = 4 * NOTE_ON_CAPACITY;
+
+/**
+ * A note event waiting to be sent, possibly in a later buffer.
+ */
+#[derive(Clone, Copy, Debug)]
+struct ScheduledNote {
+    /**
+     * The position in samples since the scheduler was created.
+     */
+    position: u64,
+    /**
+     * Keeps the events in the order they were scheduled when they share the same position.
+     */
+    order: u64,
+    note: u8,
+    channel: u8,
+    velocity: f32,
+    note_on: bool,
+    /**
+     * The note off ends a gate, so it can be held back by the pedals when it's due.
+     */
+    gate: bool,
+}
+
+/**
+ * Holds the note events that must be sent later than the current buffer,
+ * so they can be carried over to the next calls to process.
+ */
+pub(crate) struct NoteScheduler {
+    events: Vec<ScheduledNote>,
+    time: u64,
+    next_order: u64,
+}
+
+impl Default for NoteScheduler {
+    fn default() -> Self {
+        Self {
+            events: Vec::with_capacity(CAPACITY),
+            time: 0,
+            next_order: 0,
+        }
+    }
+}
+
+impl NoteScheduler {
+    pub fn schedule_note_on(
+        &mut self,
+        timing: u32,
+        delay: u32,
+        note: u8,
+        channel: u8,
+        velocity: f32,
+    ) {
+        self.schedule(
+            timing as u64 + delay as u64,
+            note,
+            channel,
+            velocity,
+            true,
+            false,
+        );
+    }
+
+    pub fn schedule_note_off(&mut self, timing: u32, delay: u32, note: u8, channel: u8) {
+        self.schedule(
+            timing as u64 + delay as u64,
+            note,
+            channel,
+            0.0,
+            false,
+            false,
+        );
+    }
+
+    /**
+     * Schedules the note off that ends the gate of a note.
+     */
+    pub fn schedule_gate_off(&mut self, timing: u32, delay: u32, note: u8, channel: u8) {
+        self.schedule(
+            timing as u64 + delay as u64,
+            note,
+            channel,
+            0.0,
+            false,
+            true,
+        );
+    }
+
+    /**
+     * Removes the note ons that have not been sent yet for a note.
+     */
+    pub fn cancel_note_on(&mut self, note: u8, channel: u8) {
+        self.events
+            .retain(|event| !(event.note_on && event.note == note && event.channel == channel));
+    }
+
+    /**
+     * Removes the note offs that have not been sent yet for a note, returns true if there was one.
+     */
+    pub fn cancel_note_off(&mut self, note: u8, channel: u8) -> bool {
+        let nb_events = self.events.len();
+        self.events
+            .retain(|event| event.note_on || event.note != note || event.channel != channel);
+        self.events.len() != nb_events
+    }
+
+    /**
+     * Sends the pending note offs right away and drops the pending note ons, so no note is left hanging.
+     */
+    pub fn release_all(&mut self, context: &mut impl ProcessContext<MidiTransposer>) {
+        for event in self.events.drain(..).filter(|event| !event.note_on) {
+            context.send_event(NoteEvent::NoteOff {
+                note: event.note,
+                channel: event.channel,
+                velocity: 0.0,
+                voice_id: None,
+                timing: 0,
+            });
+        }
+    }
+
+    pub fn has_pending_events(&self) -> bool {
+        !self.events.is_empty()
+    }
+
+    /**
+     * Sends all the events that fall in the current buffer and moves the time forward.
+     * The note offs of the gates are not sent when `hold_gate_off` keeps them for later.
+     */
+    pub fn flush(
+        &mut self,
+        context: &mut impl ProcessContext<MidiTransposer>,
+        nb_samples: usize,
+        hold_gate_off: impl FnMut(u8, u8) -> bool,
+    ) {
+        self.drain_due(nb_samples, hold_gate_off, |event| context.send_event(event));
+    }
+
+    /**
+     * Passes the events that fall in the current buffer to `send`, and moves the time forward.
+     */
+    fn drain_due(
+        &mut self,
+        nb_samples: usize,
+        mut hold_gate_off: impl FnMut(u8, u8) -> bool,
+        mut send: impl FnMut(PluginNoteEvent<MidiTransposer>),
+    ) {
+        let buffer_end = self.time + nb_samples as u64;
+        self.events
+            .sort_unstable_by_key(|event| (event.position, event.order));
+        let nb_due = self
+            .events
+            .iter()
+            .take_while(|event| event.position < buffer_end)
+            .count();
+        for event in self.events.drain(..nb_due) {
+            let timing = (event.position - self.time) as u32;
+            if event.note_on {
+                send(NoteEvent::NoteOn {
+                    note: event.note,
+                    channel: event.channel,
+                    velocity: event.velocity,
+                    voice_id: None,
+                    timing,
+                });
+            } else if !(event.gate && hold_gate_off(event.note, event.channel)) {
+                send(NoteEvent::NoteOff {
+                    note: event.note,
+                    channel: event.channel,
+                    velocity: 0.0,
+                    voice_id: None,
+                    timing,
+                });
+            }
+        }
+        self.time = buffer_end;
+    }
+
+    /**
+     * Queues an event at an offset in samples from the start of the current buffer.
+     */
+    fn schedule(
+        &mut self,
+        offset: u64,
+        note: u8,
+        channel: u8,
+        velocity: f32,
+        note_on: bool,
+        gate: bool,
+    ) {
+        // Never grow the queue on the audio thread. A note on that doesn't fit is dropped,
+        // which leaves nothing hanging, but the note offs always find some room.
+        if note_on && self.events.len() >= NOTE_ON_CAPACITY {
+            return;
+        }
+        if !note_on && self.events.len() == CAPACITY {
+            self.make_room_for_note_off();
+        }
+        self.events.push(ScheduledNote {
+            position: self.time + offset,
+            order: self.next_order,
+            note,
+            channel,
+            velocity,
+            note_on,
+            gate,
+        });
+        self.next_order += 1;
+    }
+
+    /**
+     * Drops the latest note on, whose note will never be started.
+     * If there are only note offs, two of the same note are merged into the earliest one.
+     */
+    fn make_room_for_note_off(&mut self) {
+        if let Some(index) = self.events.iter().rposition(|event| event.note_on) {
+            self.events.swap_remove(index);
+            return;
+        }
+        let mut seen = [0u128; 16];
+        let duplicate = self.events.iter().position(|event| {
+            let channel_notes = &mut seen[event.channel as usize & 15];
+            let seen_before = *channel_notes & (1 << event.note) != 0;
+            *channel_notes |= 1 << event.note;
+            seen_before
+        });
+        if let Some(index) = duplicate {
+            let removed = self.events.swap_remove(index);
+            if let Some(kept) = self
+                .events
+                .iter_mut()
+                .find(|event| event.note == removed.note && event.channel == removed.channel)
+            {
+                kept.position = kept.position.min(removed.position);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(
+        scheduler: &mut NoteScheduler,
+        nb_samples: usize,
+    ) -> Vec<PluginNoteEvent<MidiTransposer>> {
+        let mut events = Vec::new();
+        scheduler.drain_due(nb_samples, |_, _| false, |event| events.push(event));
+        events
+    }
+
+    fn note_on(timing: u32, note: u8) -> PluginNoteEvent<MidiTransposer> {
+        NoteEvent::NoteOn {
+            note,
+            channel: 0,
+            velocity: 1.0,
+            voice_id: None,
+            timing,
+        }
+    }
+
+    fn note_off(timing: u32, note: u8) -> PluginNoteEvent<MidiTransposer> {
+        NoteEvent::NoteOff {
+            note,
+            channel: 0,
+            velocity: 0.0,
+            voice_id: None,
+            timing,
+        }
+    }
+
+    #[test]
+    fn the_delays_are_carried_over_to_the_next_buffers() {
+        let mut scheduler = NoteScheduler::default();
+        scheduler.schedule_note_on(100, 500, 60, 0, 1.0);
+        scheduler.schedule_note_off(200, 0, 62, 0);
+
+        assert_eq!(drain(&mut scheduler, 256), vec![note_off(200, 62)]);
+        assert_eq!(drain(&mut scheduler, 256), vec![]);
+        assert!(scheduler.has_pending_events());
+        // 600 samples after the start of the first buffer.
+        assert_eq!(drain(&mut scheduler, 256), vec![note_on(88, 60)]);
+        assert!(!scheduler.has_pending_events());
+    }
+
+    #[test]
+    fn the_events_at_the_same_position_keep_their_order() {
+        let mut scheduler = NoteScheduler::default();
+        scheduler.schedule_note_off(10, 0, 60, 0);
+        scheduler.schedule_note_on(0, 10, 60, 0, 1.0);
+        assert_eq!(
+            drain(&mut scheduler, 64),
+            vec![note_off(10, 60), note_on(10, 60)]
+        );
+    }
+
+    #[test]
+    fn cancels_the_pending_note_ons_and_note_offs() {
+        let mut scheduler = NoteScheduler::default();
+        scheduler.schedule_note_on(0, 300, 60, 0, 1.0);
+        scheduler.schedule_note_off(0, 400, 60, 0);
+        scheduler.schedule_note_on(0, 300, 60, 1, 1.0);
+
+        scheduler.cancel_note_on(60, 0);
+        assert!(scheduler.cancel_note_off(60, 0));
+        assert!(!scheduler.cancel_note_off(60, 0));
+        // The note on the other channel is still played.
+        let events = drain(&mut scheduler, 512);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], NoteEvent::NoteOn { channel: 1, .. }));
+    }
+
+    #[test]
+    fn holds_back_the_gate_offs() {
+        let mut scheduler = NoteScheduler::default();
+        scheduler.schedule_gate_off(0, 10, 60, 0);
+        scheduler.schedule_note_off(0, 20, 62, 0);
+        let mut events = Vec::new();
+        scheduler.drain_due(64, |note, _| note == 60, |event| events.push(event));
+        assert_eq!(events, vec![note_off(20, 62)]);
+    }
+
+    #[test]
+    fn the_note_ons_only_take_part_of_the_queue() {
+        let mut scheduler = NoteScheduler::default();
+        for i in 0..NOTE_ON_CAPACITY + 1 {
+            scheduler.schedule_note_on(0, i as u32, (i % 128) as u8, 0, 1.0);
+        }
+        assert_eq!(scheduler.events.len(), NOTE_ON_CAPACITY);
+    }
+
+    #[test]
+    fn a_note_off_drops_the_latest_note_on_when_the_queue_is_full() {
+        let mut scheduler = NoteScheduler::default();
+        for i in 0..NOTE_ON_CAPACITY {
+            scheduler.schedule_note_on(0, i as u32, 60, 0, 1.0);
+        }
+        for i in NOTE_ON_CAPACITY..CAPACITY {
+            scheduler.schedule_note_off(0, i as u32, 61, 0);
+        }
+        scheduler.schedule_note_off(0, 0, 62, 0);
+
+        assert_eq!(scheduler.events.len(), CAPACITY);
+        assert!(scheduler.events.iter().any(|event| event.note == 62));
+        let nb_note_ons = scheduler
+            .events
+            .iter()
+            .filter(|event| event.note_on)
+            .count();
+        assert_eq!(nb_note_ons, NOTE_ON_CAPACITY - 1);
+        // The latest note on was dropped.
+        let latest_note_on = scheduler
+            .events
+            .iter()
+            .filter(|event| event.note_on)
+            .map(|event| event.position)
+            .max();
+        assert_eq!(latest_note_on, Some(NOTE_ON_CAPACITY as u64 - 2));
+    }
+
+    #[test]
+    fn the_note_offs_of_the_same_note_are_merged_when_the_queue_is_full() {
+        let mut scheduler = NoteScheduler::default();
+        scheduler.schedule_note_off(0, 10_000, 0, 0);
+        for i in 1..CAPACITY {
+            scheduler.schedule_note_off(0, 10 + i as u32, (i % 128) as u8, 0);
+        }
+        scheduler.schedule_note_off(0, 0, 127, 1);
+
+        assert_eq!(scheduler.events.len(), CAPACITY);
+        assert!(scheduler.events.iter().any(|event| event.channel == 1));
+        // The second note off of note 0 was merged into the first one, which is sent as early.
+        let earliest = scheduler
+            .events
+            .iter()
+            .filter(|event| event.note == 0)
+            .map(|event| event.position)
+            .min();
+        assert_eq!(earliest, Some(138));
+    }
+}
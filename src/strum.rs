@@ -0,0 +1,49 @@
+use nih_plug::prelude::Enum;
+
+use crate::Chord;
+
+/**
+ * The order in which the notes of a chord are strummed.
+ */
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum StrumDirection {
+    #[name = "Up"]
+    Up,
+    #[name = "Down"]
+    Down,
+    #[name = "Alternating"]
+    Alternating,
+    /**
+     * The loudest notes are played first.
+     */
+    #[name = "By velocity"]
+    ByVelocity,
+}
+
+/**
+ * Sorts the notes of the chord in the order they should be strummed.
+ * Returns the sorted notes and how many there are, without allocating.
+ */
+pub(crate) fn strum_order(
+    chord: &Chord,
+    direction: StrumDirection,
+    up: bool,
+) -> ([u8; 128], usize) {
+    let (mut notes, count) = chord.sorted_notes();
+    match direction {
+        StrumDirection::Up => {}
+        StrumDirection::Down => notes[..count].reverse(),
+        StrumDirection::Alternating => {
+            if !up {
+                notes[..count].reverse();
+            }
+        }
+        StrumDirection::ByVelocity => notes[..count].sort_unstable_by(|a, b| {
+            chord
+                .velocity(*b)
+                .total_cmp(&chord.velocity(*a))
+                .then(a.cmp(b))
+        }),
+    }
+    (notes, count)
+}
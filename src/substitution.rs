@@ -0,0 +1,123 @@
+use nih_plug::prelude::Enum;
+
+use crate::{
+    chord_recognizer::{self, RecognizedChord},
+    note_range::NoteRange,
+    params::{MidiTransposerParams, RuleParams},
+    random::Random,
+    Chord,
+};
+
+/**
+ * When a substitution rule is applied.
+ */
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum RuleCondition {
+    #[name = "Off"]
+    Off,
+    #[name = "Always"]
+    Always,
+    /**
+     * On each chord, with the probability of the rule.
+     */
+    #[name = "Probability"]
+    Probability,
+    /**
+     * While the CC of the rule is held on the input channel.
+     */
+    #[name = "CC held"]
+    CcHeld,
+}
+
+/**
+ * Applies the substitution rules whose condition is met to a chord, one after the other.
+ * The rules only change the chords they recognize, like the dominant chords for the tritone substitution.
+ */
+pub(crate) fn apply(
+    chord: &Chord,
+    params: &MidiTransposerParams,
+    ccs_held: u128,
+    random: &mut Random,
+) -> Chord {
+    let range = NoteRange::from_params(params);
+    let mut chord = *chord;
+    if triggered(&params.tritone_sub, ccs_held, random) {
+        chord = tritone_substitute(&chord, &range);
+    }
+    if triggered(&params.relative_sub, ccs_held, random) {
+        chord = relative_swap(&chord, &range);
+    }
+    if triggered(&params.diminished_sub, ccs_held, random) {
+        chord = diminished_passing(&chord, &range);
+    }
+    chord
+}
+
+fn triggered(rule: &RuleParams, ccs_held: u128, random: &mut Random) -> bool {
+    match rule.condition.value() {
+        RuleCondition::Off => false,
+        RuleCondition::Always => true,
+        RuleCondition::Probability => random.next_f32() < rule.probability.value(),
+        RuleCondition::CcHeld => ccs_held & (1 << rule.cc.value()) != 0,
+    }
+}
+
+/**
+ * A dominant chord is replaced by the dominant chord a tritone away, G7 becomes Db7.
+ */
+fn tritone_substitute(chord: &Chord, range: &NoteRange) -> Chord {
+    match chord_recognizer::recognize_chord(chord) {
+        Some(recognized) if is_dominant(&recognized) => chord.map_notes(range, |note| note - 6),
+        _ => *chord,
+    }
+}
+
+/**
+ * A major chord becomes its relative minor by raising its fifth a whole tone, C becomes Am/C.
+ * A minor chord becomes its relative major by lowering its root a whole tone, Am becomes C/G.
+ */
+fn relative_swap(chord: &Chord, range: &NoteRange) -> Chord {
+    let Some(recognized) = chord_recognizer::recognize_chord(chord) else {
+        return *chord;
+    };
+    let (tone, shift) = if has_interval(&recognized, 4) && has_interval(&recognized, 7) {
+        (7, 2)
+    } else if has_interval(&recognized, 3) && has_interval(&recognized, 7) {
+        (0, -2)
+    } else {
+        return *chord;
+    };
+    let tone_class = (recognized.root + tone) % 12;
+    chord.map_notes(range, |note| {
+        if note % 12 == tone_class as i32 {
+            note + shift
+        } else {
+            note
+        }
+    })
+}
+
+/**
+ * A dominant chord is replaced by the diminished seventh chord on its third,
+ * by raising its root a semitone, G7 becomes B°7.
+ */
+fn diminished_passing(chord: &Chord, range: &NoteRange) -> Chord {
+    match chord_recognizer::recognize_chord(chord) {
+        Some(recognized) if is_dominant(&recognized) => chord.map_notes(range, |note| {
+            if note % 12 == recognized.root as i32 {
+                note + 1
+            } else {
+                note
+            }
+        }),
+        _ => *chord,
+    }
+}
+
+fn is_dominant(recognized: &RecognizedChord) -> bool {
+    has_interval(recognized, 4) && has_interval(recognized, 10)
+}
+
+fn has_interval(recognized: &RecognizedChord, interval: u8) -> bool {
+    recognized.intervals & (1 << interval) != 0
+}
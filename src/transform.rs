@@ -0,0 +1,83 @@
+use nih_plug::prelude::Enum;
+
+use crate::{note_range::NoteRange, params::MidiTransposerParams, Chord};
+
+/**
+ * A reharmonisation applied to the chords once they are built.
+ */
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    #[name = "Off"]
+    Off,
+    /**
+     * Mirrors the notes around the axis between the third and the fifth of the key center,
+     * so C becomes G and E becomes Eb in C.
+     */
+    #[name = "Negative harmony"]
+    NegativeHarmony,
+    /**
+     * Mirrors the notes around the pivot note.
+     */
+    #[name = "Chromatic inversion"]
+    ChromaticInversion,
+}
+
+/**
+ * Applies the transform of the params to a chord, then fits it in the register.
+ * The velocity and channel of each note follow it to its mirrored note.
+ */
+pub(crate) fn apply(chord: &Chord, params: &MidiTransposerParams) -> Chord {
+    // Every note n becomes mirror - n.
+    let mirror = match params.transform.value() {
+        Transform::Off => return *chord,
+        Transform::NegativeHarmony => {
+            let (lowest, highest) = (
+                chord.notes.trailing_zeros() as i32,
+                127 - chord.notes.leading_zeros() as i32,
+            );
+            // The mirrors an octave apart give the same pitch classes,
+            // the one closest to the chord keeps it in its register.
+            let axis = 2 * params.key_center.value() + 7;
+            let octaves = ((lowest + highest - axis) as f32 / 12.0).round() as i32;
+            axis + 12 * octaves
+        }
+        Transform::ChromaticInversion => 2 * params.pivot.value(),
+    };
+
+    chord.map_notes(&NoteRange::from_params(params), |note| mirror - note)
+}
+
+/**
+ * Puts the notes of the arp, sorted from the lowest to the highest, in the order they are played.
+ * Returns true if the order was changed, when the notes were in the other order.
+ */
+pub(crate) fn order_arp_notes(notes: &mut [u8], retrograde: bool) -> bool {
+    let descending = notes.windows(2).any(|pair| pair[0] > pair[1]);
+    if notes.len() > 1 && descending != retrograde {
+        notes.reverse();
+        return true;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_retrograde_reverses_the_arp_order() {
+        let mut notes = [60, 64, 67];
+        assert!(!order_arp_notes(&mut notes, false));
+        assert!(order_arp_notes(&mut notes, true));
+        assert_eq!(notes, [67, 64, 60]);
+        assert!(!order_arp_notes(&mut notes, true));
+        assert!(order_arp_notes(&mut notes, false));
+        assert_eq!(notes, [60, 64, 67]);
+    }
+
+    #[test]
+    fn the_order_of_a_single_note_never_changes() {
+        assert!(!order_arp_notes(&mut [], true));
+        assert!(!order_arp_notes(&mut [60], true));
+    }
+}
@@ -0,0 +1,39 @@
+use nih_plug::prelude::Enum;
+
+use crate::params::MidiTransposerParams;
+
+/**
+ * How the velocity of the triggering note is turned into the chord velocity.
+ */
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum VelocityMode {
+    #[name = "Follow input"]
+    Follow,
+    #[name = "Fixed"]
+    Fixed,
+    #[name = "Compressed"]
+    Compressed,
+}
+
+/**
+ * Computes the base velocity of a chord from the velocity of the note that triggered it.
+ * The per note scaling is applied on top of it.
+ */
+pub(crate) fn base_velocity(params: &MidiTransposerParams, input_velocity: f32) -> f32 {
+    match params.velocity_mode.value() {
+        VelocityMode::Follow => input_velocity,
+        VelocityMode::Fixed => params.fixed_velocity.value(),
+        VelocityMode::Compressed => {
+            let min = params.velocity_min.value();
+            let max = params.velocity_max.value();
+            min + input_velocity * (max - min)
+        }
+    }
+}
+
+/**
+ * Applies a velocity scale and keeps the result in the midi velocity range.
+ */
+pub(crate) fn scale(velocity: f32, scale: f32) -> f32 {
+    (velocity * scale).clamp(0.0, 1.0)
+}
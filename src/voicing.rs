@@ -0,0 +1,229 @@
+use nih_plug::prelude::Enum;
+
+use crate::{random::Random, Chord};
+
+/**
+ * The inversion applied to the stacked chord.
+ * Each step moves the lowest note of the chord up an octave.
+ */
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum Inversion {
+    #[name = "Root position"]
+    Root,
+    #[name = "1st inversion"]
+    First,
+    #[name = "2nd inversion"]
+    Second,
+    #[name = "3rd inversion"]
+    Third,
+}
+
+impl Inversion {
+    fn steps(self) -> usize {
+        match self {
+            Inversion::Root => 0,
+            Inversion::First => 1,
+            Inversion::Second => 2,
+            Inversion::Third => 3,
+        }
+    }
+}
+
+/**
+ * The voicing applied to the stacked chord once it has been inverted.
+ */
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum Voicing {
+    #[name = "Close"]
+    Close,
+    #[name = "Drop 2"]
+    Drop2,
+    #[name = "Drop 3"]
+    Drop3,
+    #[name = "Drop 2+4"]
+    Drop24,
+    #[name = "Open/Spread"]
+    Spread,
+}
+
+/**
+ * What is drawn at random on each trigger, instead of the selected inversion and voicing.
+ */
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum Variation {
+    #[name = "Off"]
+    Off,
+    #[name = "Random inversion"]
+    Inversion,
+    #[name = "Random voicing"]
+    Voicing,
+    #[name = "Random inversion and voicing"]
+    Both,
+}
+
+/**
+ * Returns the inversion and voicing to use for a trigger, drawn at random when the variation asks for it.
+ */
+pub(crate) fn vary(
+    inversion: Inversion,
+    voicing: Voicing,
+    variation: Variation,
+    random: &mut Random,
+) -> (Inversion, Voicing) {
+    let inversion = match variation {
+        Variation::Inversion | Variation::Both => random_variant(random),
+        _ => inversion,
+    };
+    let voicing = match variation {
+        Variation::Voicing | Variation::Both => random_variant(random),
+        _ => voicing,
+    };
+    (inversion, voicing)
+}
+
+fn random_variant<T: Enum>(random: &mut Random) -> T {
+    T::from_index(random.next_u64() as usize % T::variants().len())
+}
+
+/**
+ * Applies the inversion and then the voicing to a chord.
+ * The velocity and channel of each note follow it when it's moved to another octave.
+ */
+pub(crate) fn apply(chord: &Chord, inversion: Inversion, voicing: Voicing) -> Chord {
+    apply_voicing(&invert(chord, inversion), voicing)
+}
+
+fn invert(chord: &Chord, inversion: Inversion) -> Chord {
+    let mut inverted = *chord;
+    for _ in 0..inversion.steps() {
+        if inverted.notes == 0 {
+            break;
+        }
+        let lowest = inverted.notes.trailing_zeros() as u8;
+        // Don't move the note if it would go out of the midi range.
+        if lowest + 12 > 127 {
+            break;
+        }
+        let moved = inverted;
+        inverted.remove_note(lowest);
+        inverted.add_voices(&moved, lowest, lowest + 12);
+    }
+    inverted
+}
+
+fn apply_voicing(chord: &Chord, voicing: Voicing) -> Chord {
+    if voicing == Voicing::Close {
+        return *chord;
+    }
+
+    let (sorted, count) = chord.sorted_notes();
+    let mut voiced = Chord::default();
+    for (index, note) in sorted[..count].iter().enumerate() {
+        // 1 is the top note, 2 the one just below, etc.
+        let from_top = count - index;
+        let shift: i32 = match voicing {
+            Voicing::Drop2 if from_top == 2 => -12,
+            Voicing::Drop3 if from_top == 3 => -12,
+            Voicing::Drop24 if from_top == 2 || from_top == 4 => -12,
+            // Raise every other note from the bottom to spread the chord.
+            Voicing::Spread if index % 2 == 1 => 12,
+            _ => 0,
+        };
+        let voiced_note = *note as i32 + shift;
+        if (0..128).contains(&voiced_note) {
+            voiced.add_voices(chord, *note, voiced_note as u8);
+        } else {
+            voiced.add_voices(chord, *note, *note);
+        }
+    }
+    voiced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(notes: &[u8]) -> Chord {
+        let mut chord = Chord::default();
+        for note in notes {
+            chord.add_note(*note, 1.0, 0);
+        }
+        chord
+    }
+
+    fn voiced(notes: &[u8], inversion: Inversion, voicing: Voicing) -> Vec<u8> {
+        let (sorted, count) = apply(&chord(notes), inversion, voicing).sorted_notes();
+        sorted[..count].to_vec()
+    }
+
+    const CMAJ7: [u8; 4] = [60, 64, 67, 71];
+
+    #[test]
+    fn each_inversion_moves_the_lowest_note_up_an_octave() {
+        let inverted = |inversion| voiced(&CMAJ7, inversion, Voicing::Close);
+        assert_eq!(inverted(Inversion::Root), vec![60, 64, 67, 71]);
+        assert_eq!(inverted(Inversion::First), vec![64, 67, 71, 72]);
+        assert_eq!(inverted(Inversion::Second), vec![67, 71, 72, 76]);
+        assert_eq!(inverted(Inversion::Third), vec![71, 72, 76, 79]);
+    }
+
+    #[test]
+    fn the_drop_voicings_move_the_notes_from_the_top_down_an_octave() {
+        let dropped = |voicing| voiced(&CMAJ7, Inversion::Root, voicing);
+        assert_eq!(dropped(Voicing::Drop2), vec![55, 60, 64, 71]);
+        assert_eq!(dropped(Voicing::Drop3), vec![52, 60, 67, 71]);
+        assert_eq!(dropped(Voicing::Drop24), vec![48, 55, 64, 71]);
+    }
+
+    #[test]
+    fn the_spread_voicing_raises_every_other_note() {
+        assert_eq!(
+            voiced(&CMAJ7, Inversion::Root, Voicing::Spread),
+            vec![60, 67, 76, 83]
+        );
+    }
+
+    #[test]
+    fn the_voicing_applies_after_the_inversion() {
+        // E G B C, with the B dropped.
+        assert_eq!(
+            voiced(&CMAJ7, Inversion::First, Voicing::Drop2),
+            vec![59, 64, 67, 72]
+        );
+    }
+
+    #[test]
+    fn the_notes_are_not_moved_out_of_the_midi_range() {
+        assert_eq!(
+            voiced(&[116, 120], Inversion::First, Voicing::Close),
+            vec![116, 120]
+        );
+        // The first two steps fit, the third one doesn't.
+        assert_eq!(
+            voiced(&[110, 115], Inversion::Third, Voicing::Close),
+            vec![122, 127]
+        );
+        assert_eq!(
+            voiced(&[5, 9, 12], Inversion::Root, Voicing::Drop2),
+            vec![5, 9, 12]
+        );
+        assert_eq!(
+            voiced(&[5, 9, 12], Inversion::Root, Voicing::Drop3),
+            vec![5, 9, 12]
+        );
+        assert_eq!(
+            voiced(&[110, 120], Inversion::Root, Voicing::Spread),
+            vec![110, 120]
+        );
+        assert_eq!(voiced(&[], Inversion::Third, Voicing::Drop24), vec![]);
+    }
+
+    #[test]
+    fn the_velocity_and_channel_follow_the_moved_notes() {
+        let mut chord = chord(&[64, 67]);
+        chord.add_note(60, 0.5, 2);
+        let inverted = apply(&chord, Inversion::First, Voicing::Close);
+        assert!(!inverted.contains(60));
+        assert_eq!(inverted.voices(72).collect::<Vec<_>>(), vec![(2, 0.5)]);
+    }
+}
@@ -0,0 +1,40 @@
+use nih_plug::prelude::Enum;
+
+use crate::params::NoteParam;
+
+/**
+ * What a keyboard zone does with the notes played in it.
+ */
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum ZoneMode {
+    #[name = "Chords"]
+    Chords,
+    /**
+     * The notes are sent as they are, only remapped to the zone output channel.
+     */
+    #[name = "Pass-through"]
+    PassThrough,
+}
+
+/**
+ * The mapping used by the keys of a zone of the keyboard.
+ */
+pub(crate) struct Zone<'a> {
+    pub mode: ZoneMode,
+    pub notes: &'a [NoteParam; 12],
+    pub octave_transpose: i32,
+    pub out_channel: i32,
+}
+
+impl Zone<'_> {
+    /**
+     * The output channel will be the same as the input channel if the output channel param is set to 0.
+     * Otherwise, it will be the value of the output channel param - 1 (because channels go from 0 to 15).
+     */
+    pub fn output_channel(&self, input_channel: Option<u8>) -> u8 {
+        match self.out_channel {
+            0 => input_channel.unwrap_or(0),
+            _ => self.out_channel as u8 - 1,
+        }
+    }
+}